@@ -0,0 +1,199 @@
+use serde::Deserialize;
+
+use crate::{
+    ai::{decay, Ai, AiProfile, AiProfiles},
+    clock::SimTime,
+    config::ConfigError,
+    controller::{ControlContext, PaddleController, PaddleIntent},
+    events::GameEvent,
+    rng::Rng,
+};
+
+// How much each point moves the running estimate of the opponent's point rate.
+const RATE_SMOOTHING: f32 = 0.2;
+// Change of the difficulty per point, per unit of distance from the target rate.
+const ADJUST_GAIN: f32 = 0.35;
+// Seconds for the effective difficulty to cover most of the way to a new difficulty.
+const DIFFICULTY_EASING: f64 = 3.0;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AdaptiveSettings {
+    // Share of the points the opponent of the adaptive AI should win.
+    pub target: f32,
+    pub easiest: String,
+    pub hardest: String,
+}
+
+impl Default for AdaptiveSettings {
+    fn default() -> Self {
+        Self {
+            target: 0.5,
+            easiest: "Raphael".to_owned(),
+            hardest: "Sarah".to_owned(),
+        }
+    }
+}
+
+impl AdaptiveSettings {
+    pub fn validate(&self, profiles: &AiProfiles) -> Result<(), ConfigError> {
+        if !(0.0..=1.0).contains(&self.target) {
+            return Err(ConfigError::Invalid(
+                "adaptive: target must be between 0 and 1".to_owned(),
+            ));
+        }
+        for name in [&self.easiest, &self.hardest] {
+            match profiles.get(name) {
+                Some(profile) if profile.is_tunable() => {}
+                Some(_) => {
+                    return Err(ConfigError::Invalid(format!(
+                        "adaptive: profile {:?} uses a policy or a script and can't be adjusted",
+                        name
+                    )))
+                }
+                None => {
+                    return Err(ConfigError::Invalid(format!(
+                        "adaptive: no profile named {:?}",
+                        name
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// An AI that keeps its opponent winning about `target` of the points by blending between an easy
+// and a hard profile, based on the points played so far and how long the rallies last.
+pub struct AdaptiveAi {
+    ai: Ai<'static>,
+    easiest: AiProfile,
+    hardest: AiProfile,
+    target: f32,
+
+    pub difficulty: f32,
+    pub target_difficulty: f32,
+    pub opponent_rate: f32,
+    pub points: (u32, u32),
+    pub average_rally: f32,
+    rally_hits: u32,
+    last_update: Option<SimTime>,
+}
+
+impl AdaptiveAi {
+    // Uses the default settings when the profiles file has no [adaptive] section, and returns
+    // None if the profiles they name don't exist.
+    pub fn new(profiles: &AiProfiles) -> Option<Self> {
+        let settings = profiles.adaptive.clone().unwrap_or_default();
+        let easiest = profiles.get(&settings.easiest)?.clone();
+        let hardest = profiles.get(&settings.hardest)?.clone();
+
+        let mut ai = Ai::from_profile(&easiest.lerp(&hardest, 0.5));
+        ai.name = "Adaptive".into();
+
+        Some(Self {
+            ai,
+            easiest,
+            hardest,
+            target: settings.target,
+
+            difficulty: 0.5,
+            target_difficulty: 0.5,
+            opponent_rate: settings.target,
+            points: (0, 0),
+            average_rally: 0.0,
+            rally_hits: 0,
+            last_update: None,
+        })
+    }
+}
+
+impl AdaptiveAi {
+    fn point_played(&mut self, opponent_scored: bool) {
+        if opponent_scored {
+            self.points.1 += 1;
+        } else {
+            self.points.0 += 1;
+        }
+        let outcome = if opponent_scored { 1.0 } else { 0.0 };
+        self.opponent_rate += (outcome - self.opponent_rate) * RATE_SMOOTHING;
+
+        let rally = self.rally_hits as f32;
+        self.average_rally += (rally - self.average_rally) * RATE_SMOOTHING;
+        self.rally_hits = 0;
+
+        // Recent points react fast, the whole match keeps the score differential in check.
+        let match_rate = self.points.1 as f32 / (self.points.0 + self.points.1) as f32;
+        let error = (self.opponent_rate - self.target + match_rate - self.target) / 2.0;
+
+        // Losing a point in a much shorter rally than usual means the opponent was overwhelmed,
+        // so ease off faster than after a close point.
+        let urgency = if !opponent_scored && rally < self.average_rally / 2.0 {
+            1.5
+        } else {
+            1.0
+        };
+
+        self.target_difficulty =
+            (self.target_difficulty + error * ADJUST_GAIN * urgency).clamp(0.0, 1.0);
+    }
+
+    fn ease_difficulty(&mut self, now: SimTime) {
+        let elapsed = self.last_update.map_or(0.0, |last| now.since(last));
+        self.last_update = Some(now);
+
+        let t = 1.0 - decay((elapsed / DIFFICULTY_EASING) as f32);
+        self.difficulty += (self.target_difficulty - self.difficulty) * t;
+        self.ai
+            .apply_profile(&self.easiest.lerp(&self.hardest, self.difficulty));
+    }
+}
+
+impl PaddleController for AdaptiveAi {
+    fn name(&self) -> Option<&str> {
+        Some(&self.ai.name)
+    }
+
+    fn seed(&mut self, rng: Rng) {
+        self.ai.seed(rng);
+    }
+
+    fn control(&mut self, context: &ControlContext) -> PaddleIntent {
+        for event in context.events {
+            match *event {
+                GameEvent::PaddleHit { .. } => self.rally_hits += 1,
+                GameEvent::Goal { scorer } => self.point_played(scorer != context.side),
+                _ => {}
+            }
+        }
+        self.ease_difficulty(context.now);
+
+        self.ai.control(context)
+    }
+
+    fn ai(&self) -> Option<&Ai<'static>> {
+        Some(&self.ai)
+    }
+
+    fn ai_mut(&mut self) -> Option<&mut Ai<'static>> {
+        Some(&mut self.ai)
+    }
+
+    fn status(&self) -> Option<String> {
+        let logic = &self.ai.logic;
+        Some(format!(
+            "difficulty {:.2} -> {:.2}\n\
+             accuracy {:.2}, reaction {}ms, speed {:.2}\n\
+             opponent points {:.0}% (target {:.0}%)\n\
+             average rally {:.1} hits",
+            self.difficulty,
+            self.target_difficulty,
+            logic.accuracy,
+            logic.reaction_time,
+            logic.max_speed,
+            self.opponent_rate * 100.0,
+            self.target * 100.0,
+            self.average_rally,
+        ))
+    }
+}
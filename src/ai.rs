@@ -1,23 +1,306 @@
-use macroquad::{
-    prelude::{get_time, vec2, Vec2},
-    rand::gen_range,
-};
+use std::{borrow::Cow, collections::HashSet, fs, io::Write, sync::Arc};
+
+use macroquad::prelude::{vec2, Vec2};
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    adaptive::AdaptiveSettings,
     bounds::Bounds,
-    constants::{BALL_RADIUS, BALL_SIZE, BOUNDS, PLAYER_WIDTH},
-    physics::CollisionType,
+    clock::SimTime,
+    config::{load_toml, serialize_f32, serialize_f32_pair, ConfigError},
+    constants::{
+        BALL_RADIUS, BALL_SIZE, PLAYER_HEIGHT, PLAYER_WIDTH, RAPHAEL, SARAH, SERVE_SPEED, TICK_RATE,
+    },
+    controller::{ControlContext, PaddleController, PaddleIntent},
+    events::{GameEvent, Side},
+    learning::{PolicyController, QPolicy},
+    physics::GameObject,
+    rng::Rng,
+    script::{check_script, ScriptController},
+    telemetry::{Decision, DecisionLog, Outcome},
 };
 
+// The tunable part of an AI, as written in the profiles file. A profile with a `policy` file is
+// played by the trained policy instead of `AiLogic`, and one with a `script` by the script.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiProfile {
+    pub name: String,
+    #[serde(serialize_with = "serialize_f32_pair")]
+    pub hit_range: (f32, f32),
+    #[serde(serialize_with = "serialize_f32")]
+    pub accuracy: f32,
+    pub reaction_time: u16,
+    #[serde(default = "AiProfile::max_speed", serialize_with = "serialize_f32")]
+    pub max_speed: f32,
+    #[serde(default, serialize_with = "serialize_f32")]
+    pub anticipation: f32,
+    #[serde(default, serialize_with = "serialize_f32")]
+    pub aggression: f32,
+    #[serde(default)]
+    pub prediction: PredictionMode,
+    #[serde(default, serialize_with = "serialize_f32")]
+    pub speed_reaction: f32,
+    #[serde(default, serialize_with = "serialize_f32")]
+    pub fatigue: f32,
+    #[serde(default, serialize_with = "serialize_f32")]
+    pub overshoot: f32,
+    #[serde(default)]
+    pub hesitation: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+    #[serde(skip)]
+    pub learned: Option<Arc<QPolicy>>,
+}
+
+impl AiProfile {
+    fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |reason: &str| {
+            Err(ConfigError::Invalid(format!(
+                "profile {:?}: {}",
+                self.name, reason
+            )))
+        };
+
+        if self.name.trim().is_empty() {
+            return invalid("name must not be empty");
+        }
+        if self.hit_range.0 > self.hit_range.1 {
+            return invalid("hit_range must be [low, high] with low <= high");
+        }
+        if !(0.0..=1.0).contains(&self.accuracy) {
+            return invalid("accuracy must be between 0 and 1");
+        }
+        if !(self.max_speed > 0.0 && self.max_speed <= 1.0) {
+            return invalid("max_speed must be greater than 0 and at most 1");
+        }
+        if !(0.0..=1.0).contains(&self.anticipation) {
+            return invalid("anticipation must be between 0 and 1");
+        }
+        if !(0.0..=1.0).contains(&self.aggression) {
+            return invalid("aggression must be between 0 and 1");
+        }
+        if !(0.0..).contains(&self.speed_reaction) {
+            return invalid("speed_reaction must not be negative");
+        }
+        if !(0.0..).contains(&self.fatigue) {
+            return invalid("fatigue must not be negative");
+        }
+        if !(0.0..=1.0).contains(&self.overshoot) {
+            return invalid("overshoot must be between 0 and 1");
+        }
+        if self.policy.is_some() && self.script.is_some() {
+            return invalid("a profile can't have both a policy and a script");
+        }
+        if let Some(script) = &self.script {
+            check_script(script).map_err(ConfigError::Invalid)?;
+        }
+        Ok(())
+    }
+
+    fn max_speed() -> f32 {
+        1.0
+    }
+}
+
+impl AiProfile {
+    pub fn controller(&self) -> Box<dyn PaddleController> {
+        match (&self.learned, &self.script) {
+            (Some(policy), _) => Box::new(PolicyController::new(&self.name, policy.clone())),
+            (None, Some(script)) => Box::new(ScriptController::new(&self.name, script)),
+            (None, None) => Box::new(Ai::from_profile(self)),
+        }
+    }
+
+    // Whether the knobs drive this profile, rather than a trained policy or a script.
+    pub fn is_tunable(&self) -> bool {
+        self.policy.is_none() && self.script.is_none()
+    }
+
+    // Adds this profile at the end of a profiles file, leaving the rest of the file untouched.
+    pub fn append_to(&self, path: &str) -> Result<(), ConfigError> {
+        let text = toml::to_string(&AiProfiles {
+            profiles: vec![self.clone()],
+            adaptive: None,
+        })
+        .map_err(|error| ConfigError::Invalid(error.to_string()))?;
+
+        fs::OpenOptions::new()
+            .append(true)
+            .open(path)
+            .and_then(|mut file| write!(file, "\n{}", text))
+            .map_err(|error| ConfigError::Io(path.to_owned(), error))
+    }
+}
+
+impl AiProfile {
+    // Blends the knobs of two profiles, `t` going from 0.0 (this one) to 1.0 (`other`).
+    pub fn lerp(&self, other: &AiProfile, t: f32) -> AiProfile {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        AiProfile {
+            name: self.name.clone(),
+            hit_range: (
+                lerp(self.hit_range.0, other.hit_range.0),
+                lerp(self.hit_range.1, other.hit_range.1),
+            ),
+            accuracy: lerp(self.accuracy, other.accuracy),
+            reaction_time: lerp(self.reaction_time as f32, other.reaction_time as f32).round()
+                as u16,
+            max_speed: lerp(self.max_speed, other.max_speed),
+            anticipation: lerp(self.anticipation, other.anticipation),
+            aggression: lerp(self.aggression, other.aggression),
+            prediction: self.prediction,
+            speed_reaction: lerp(self.speed_reaction, other.speed_reaction),
+            fatigue: lerp(self.fatigue, other.fatigue),
+            overshoot: lerp(self.overshoot, other.overshoot),
+            hesitation: lerp(self.hesitation as f32, other.hesitation as f32).round() as u16,
+            policy: None,
+            script: None,
+            learned: None,
+        }
+    }
+}
+
+impl From<&Ai<'_>> for AiProfile {
+    fn from(ai: &Ai) -> Self {
+        Self {
+            name: ai.name.to_string(),
+            hit_range: ai.logic.hit_range,
+            accuracy: ai.logic.accuracy,
+            reaction_time: ai.logic.reaction_time,
+            max_speed: ai.logic.max_speed,
+            anticipation: ai.logic.anticipation,
+            aggression: ai.logic.aggression,
+            prediction: ai.logic.prediction,
+            speed_reaction: ai.logic.speed_reaction,
+            fatigue: ai.logic.fatigue,
+            overshoot: ai.logic.overshoot,
+            hesitation: ai.logic.hesitation,
+            policy: None,
+            script: None,
+            learned: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiProfiles {
+    #[serde(rename = "profile")]
+    pub profiles: Vec<AiProfile>,
+    #[serde(default, skip_serializing)]
+    pub adaptive: Option<AdaptiveSettings>,
+}
+
+impl Default for AiProfiles {
+    fn default() -> Self {
+        Self {
+            profiles: vec![AiProfile::from(&SARAH), AiProfile::from(&RAPHAEL)],
+            adaptive: None,
+        }
+    }
+}
+
+impl AiProfiles {
+    pub fn load(path: &str) -> Result<Self, ConfigError> {
+        let mut profiles: Self = load_toml(path)?;
+        if profiles.profiles.is_empty() {
+            return Err(ConfigError::Invalid(format!(
+                "{}: no [[profile]] defined",
+                path
+            )));
+        }
+
+        let mut names = HashSet::new();
+        for profile in &profiles.profiles {
+            profile.validate()?;
+            if !names.insert(profile.name.as_str()) {
+                return Err(ConfigError::Invalid(format!(
+                    "profile {:?} is defined more than once",
+                    profile.name
+                )));
+            }
+        }
+
+        for profile in &mut profiles.profiles {
+            if let Some(policy) = &profile.policy {
+                profile.learned = Some(Arc::new(QPolicy::load(policy)?));
+            }
+        }
+
+        if let Some(adaptive) = &profiles.adaptive {
+            adaptive.validate(&profiles)?;
+        }
+        Ok(profiles)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AiProfile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+
+    // The profile following `name`, wrapping around, or the first one if `name` is unknown.
+    pub fn next_after(&self, name: &str) -> &AiProfile {
+        let index = self
+            .profiles
+            .iter()
+            .position(|profile| profile.name == name)
+            .map_or(0, |index| (index + 1) % self.profiles.len());
+        &self.profiles[index]
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PredictionMode {
+    // Closed-form reflections off the top and bottom walls, integrating the flight only when
+    // the ball curves.
+    #[default]
+    Analytic,
+    // Runs the real physics step on a copy of the ball against the walls and the opponent's
+    // paddle, falling back to the analytic prediction if the ball never reaches the paddle.
+    Simulated,
+}
+
+const MIN_CURVING_SPIN: f32 = 0.5;
+const MAX_PREDICTION_STEPS: usize = 2400;
+const AIM_SAMPLES: usize = 16;
+const STEEP_SHOT_WEIGHT: f32 = 0.25;
+// Simulated predictions are redone after any event, and this often in seconds to follow the
+// opponent's paddle moving.
+const RESIMULATE_INTERVAL: f64 = 0.1;
+// Hesitation only kicks in when the paddle is already within this fraction of its height from
+// where it has to be.
+const HESITATION_ZONE: f32 = 0.25;
+
 pub struct AiLogic {
     pub hit_range: (f32, f32),
     pub accuracy: f32,
     pub reaction_time: u16,
+    pub max_speed: f32,
+    pub anticipation: f32,
+    pub aggression: f32,
+    pub prediction: PredictionMode,
+    pub speed_reaction: f32,
+    pub fatigue: f32,
+    pub overshoot: f32,
+    pub hesitation: u16,
 
     pub hit_position: f32,
-    pub collision_time: f64,
+    pub aim_pending: bool,
+    pub read_pending: bool,
+    // Seconds between the opponent's hit and moving, decided when reading the ball.
+    pub reaction_delay: f64,
+    // Own hits in the current rally, making a tiring AI sloppier.
+    pub rally_hits: u32,
+    // Vertical offset past the target the AI first heads to when it misjudged the ball.
+    pub overshoot_offset: f32,
+    pub collision_time: SimTime,
     pub predicted_position: Option<Vec2>,
+    pub simulated_at: SimTime,
     pub accuracy_variation: f32,
+    pub rng: Rng,
+    // Only recorded once someone starts debugging the AI.
+    pub log: Option<DecisionLog>,
 }
 
 impl AiLogic {
@@ -26,74 +309,329 @@ impl AiLogic {
             hit_range,
             accuracy,
             reaction_time,
+            max_speed: 1.0,
+            anticipation: 0.0,
+            aggression: 0.0,
+            prediction: PredictionMode::Analytic,
+            speed_reaction: 0.0,
+            fatigue: 0.0,
+            overshoot: 0.0,
+            hesitation: 0,
 
             hit_position: 0.5,
-            collision_time: 0.0,
+            aim_pending: false,
+            read_pending: false,
+            reaction_delay: reaction_time as f64 / 1000.0,
+            rally_hits: 0,
+            overshoot_offset: 0.0,
+            collision_time: SimTime::ZERO,
             predicted_position: None,
+            simulated_at: SimTime::ZERO,
             accuracy_variation: 1.0,
+            rng: Rng::new(0, 0),
+            log: None,
         }
     }
 }
 
 impl AiLogic {
-    pub fn observe(
-        &mut self,
-        player_position: Vec2,
-        ball_collisions: Vec<CollisionType>,
-        ball_position: Vec2,
-        ball_velocity: Vec2,
-    ) {
-        if !ball_collisions.is_empty() || ball_velocity.length_squared() == 0.0 {
-            self.collision_time = get_time();
+    pub fn observe(&mut self, context: &ControlContext) {
+        let ControlContext {
+            side,
+            paddle,
+            opponent,
+            ball,
+            events,
+            bounds,
+            now,
+            ..
+        } = *context;
+        let player_position = paddle.position;
+        let ball_velocity = ball.velocity;
+        for event in events {
+            if event.is_hit_by(side) {
+                self.rally_hits += 1;
+                if let Some(log) = &mut self.log {
+                    log.resolve(Outcome::Hit, ball.position.into());
+                }
+            } else if let GameEvent::Goal { scorer } = *event {
+                self.rally_hits = 0;
+                if let Some(log) = &mut self.log {
+                    if scorer != side {
+                        log.resolve(Outcome::Missed, ball.position.into());
+                    }
+                    log.end_rally(scorer);
+                }
+            }
+        }
+
+        let opponent_hit = events.iter().any(|event| event.is_hit_by(side.opponent()));
+        if opponent_hit || ball_velocity.length_squared() == 0.0 {
+            self.collision_time = now;
 
             self.hit_position = self.hit_position(ball_velocity);
+            self.aim_pending = self.aggression > 0.0;
+            self.read_pending = true;
+            self.overshoot_offset = 0.0;
 
             self.accuracy_variation = self.accuracy_variation();
 
             if ball_velocity.length_squared() == 0.0 {
-                self.collision_time = self.reaction_time as f64 / 1000.0;
+                self.collision_time = SimTime::from_secs(self.reaction_time as f64 / 1000.0);
                 self.predicted_position = None;
             }
         }
 
-        let prediction_position = if ball_velocity.x < 0.0 && player_position.x < BOUNDS.center().x
+        let prediction_position = if ball_velocity.x < 0.0 && player_position.x < bounds.center().x
         {
             player_position.x + PLAYER_WIDTH
-        } else if ball_velocity.x > 0.0 && player_position.x > BOUNDS.center().x {
+        } else if ball_velocity.x > 0.0 && player_position.x > bounds.center().x {
             player_position.x - BALL_RADIUS * 2.0
         } else {
             return;
         };
 
-        self.predicted_position = Some(self.predict_ball_position(
-            prediction_position,
-            ball_position,
-            ball_velocity,
-            BOUNDS,
-        ));
+        let predicted_position = match (self.prediction, self.predicted_position) {
+            (PredictionMode::Simulated, Some(predicted_position))
+                if events.is_empty() && now.since(self.simulated_at) < RESIMULATE_INTERVAL =>
+            {
+                predicted_position
+            }
+            (PredictionMode::Simulated, _) => {
+                self.simulated_at = now;
+                let mut obstacles = context.walls.to_vec();
+                obstacles.push(opponent);
+                self.simulate_ball_position(prediction_position, ball, &obstacles)
+                    .unwrap_or_else(|| {
+                        self.predict_ball_position(prediction_position, ball, bounds)
+                    })
+            }
+            (PredictionMode::Analytic, _) => {
+                self.predict_ball_position(prediction_position, ball, bounds)
+            }
+        };
+        self.predicted_position = Some(predicted_position);
+
+        if self.aim_pending {
+            self.aim_pending = false;
+            let aimed = self.aim(predicted_position, ball.velocity, paddle, opponent, bounds);
+            let difficulty = self.prediction_difficulty(ball_velocity);
+            self.hit_position = aimed + self.rng.gen_range(-difficulty, difficulty);
+        }
+
+        if self.read_pending {
+            self.read_pending = false;
+            self.read_ball(paddle, predicted_position, ball_velocity);
+            self.log_decision(side, ball, now);
+        }
+    }
+
+    fn log_decision(&mut self, side: Side, ball: &GameObject, now: SimTime) {
+        let decision = Decision {
+            time: now.since(SimTime::ZERO),
+            ball_position: ball.position.into(),
+            ball_velocity: ball.velocity.into(),
+            ball_spin: ball.angular_velocity,
+            prediction: self.prediction,
+            predicted_position: self.predicted_position.unwrap_or_default().into(),
+            hit_position: self.hit_position,
+            accuracy_variation: self.accuracy_variation,
+            reaction_delay: self.reaction_delay,
+            tiredness: self.tiredness(),
+            overshoot_offset: self.overshoot_offset,
+            outcome: Outcome::Pending,
+            outcome_position: None,
+        };
+        if let Some(log) = &mut self.log {
+            log.record(side, decision);
+        }
+    }
+
+    // Seconds left before the AI starts moving towards its prediction.
+    pub fn reaction_remaining(&self, now: SimTime) -> f64 {
+        (self.reaction_delay - now.since(self.collision_time)).max(0.0)
+    }
+
+    // The flight of the ball as the AI currently predicts it, from the ball to
+    // `predicted_position`, including the bounces along the way.
+    pub fn trajectory(
+        &self,
+        ball: &GameObject,
+        obstacles: &[&GameObject],
+        bounds: Bounds,
+    ) -> Vec<Vec2> {
+        let Some(predicted_position) = self.predicted_position else {
+            return Vec::new();
+        };
+        let x = predicted_position.x;
+        let mut path = Vec::new();
+        let visit = |position| path.push(position);
+        match self.prediction {
+            PredictionMode::Simulated => {
+                self.simulate_flight(x, ball, obstacles, visit);
+            }
+            PredictionMode::Analytic => {
+                let curving = ball.angular_velocity.abs() > MIN_CURVING_SPIN;
+                let spin = if curving { ball.angular_velocity } else { 0.0 };
+                self.reflect_flight(x, ball, spin, bounds, visit);
+            }
+        }
+        path.push(predicted_position);
+        path
+    }
+
+    // Decides how long to wait before moving and whether to overshoot, once the target is known.
+    // Faster balls and tiredness slow the reaction, and a paddle that is nearly in place may
+    // hesitate before making the small correction.
+    fn read_ball(&mut self, paddle: &GameObject, predicted_position: Vec2, ball_velocity: Vec2) {
+        let speed_factor = 1.0 + self.speed_reaction * (ball_velocity.length() / SERVE_SPEED - 1.0);
+        let mut delay =
+            self.reaction_time as f32 / 1000.0 * speed_factor.max(0.0) * (1.0 + self.tiredness());
+
+        let distance = self.target(predicted_position, paddle).y - paddle.position.y;
+        let zone = paddle.collider.rect.h * HESITATION_ZONE;
+        if distance.abs() < zone {
+            let closeness = 1.0 - distance.abs() / zone;
+            delay += self.rng.gen_range(0.0, self.hesitation as f32 / 1000.0) * closeness;
+        }
+        self.reaction_delay = delay as f64;
+
+        if distance != 0.0 && self.rng.next_f32() < self.overshoot {
+            let height = paddle.collider.rect.h;
+            self.overshoot_offset = distance.signum() * self.rng.gen_range(0.25, 0.5) * height;
+        }
+    }
+
+    // Where the paddle has to be to meet the ball at `hit_position`.
+    pub fn target(&self, predicted_position: Vec2, paddle: &GameObject) -> Vec2 {
+        Vec2::from(BALL_SIZE) / 2.0 + predicted_position
+            - paddle.collider.rect.size() * self.hit_position
+    }
+
+    // Goes from 0.0 when rested towards 1.0 as the rally gets longer.
+    pub fn tiredness(&self) -> f32 {
+        1.0 - decay(self.fatigue * self.rally_hits as f32)
+    }
+
+    fn effective_accuracy(&self) -> f32 {
+        self.accuracy * (1.0 - self.tiredness())
+    }
+
+    // Picks where on the paddle to meet the ball so the return lands far from the opponent or
+    // leaves at a steep angle. The paddle sends the return off with a slope, vertical over
+    // horizontal speed, of the contact offset from its center times its deflection, and the ball
+    // keeps its speed, see `bounce`. Low aggression keeps to the middle of the hit range, high
+    // aggression uses its edges.
+    pub fn aim(
+        &self,
+        predicted_position: Vec2,
+        ball_velocity: Vec2,
+        paddle: &GameObject,
+        opponent: &GameObject,
+        bounds: Bounds,
+    ) -> f32 {
+        let paddle_height = paddle.collider.rect.h;
+        let deflection = paddle.material.deflection;
+        let max_slope = (paddle_height / 2.0 * deflection).max(f32::EPSILON);
+
+        let middle = (self.hit_range.0 + self.hit_range.1) / 2.0;
+        let reach = (self.hit_range.1 - self.hit_range.0) / 2.0 * self.aggression;
+
+        let opponent_x = if opponent.position.x < bounds.center().x {
+            opponent.position.x + opponent.collider.rect.w
+        } else {
+            opponent.position.x - BALL_SIZE.0
+        };
+        let opponent_y = opponent.collider.center().y;
+        let direction = (opponent_x - predicted_position.x).signum();
+
+        let mut best = (middle, f32::NEG_INFINITY);
+        for sample in 0..AIM_SAMPLES {
+            let hit_position =
+                middle - reach + 2.0 * reach * sample as f32 / (AIM_SAMPLES - 1) as f32;
+            let slope = (hit_position - 0.5) * paddle_height * deflection;
+            let velocity = vec2(direction, slope) * ball_velocity.x.abs();
+
+            let landing =
+                self.predict_straight_position(opponent_x, predicted_position, velocity, bounds);
+            let distance = (landing.y + BALL_RADIUS - opponent_y).abs() / bounds.h;
+            let score = distance + STEEP_SHOT_WEIGHT * slope.abs() / max_slope;
+            if score > best.1 {
+                best = (hit_position, score);
+            }
+        }
+        best.0
     }
 
     pub fn prediction_difficulty(&self, ball_velocity: Vec2) -> f32 {
         if ball_velocity.length_squared() != 0.0 {
-            (ball_velocity.y / ball_velocity.x).abs() * (1.0 - self.accuracy)
+            (ball_velocity.y / ball_velocity.x).abs() * (1.0 - self.effective_accuracy())
         } else {
             0.0
         }
     }
 
-    pub fn hit_position(&self, ball_velocity: Vec2) -> f32 {
-        gen_range(
-            self.hit_range.0 - self.prediction_difficulty(ball_velocity),
-            self.hit_range.1 + self.prediction_difficulty(ball_velocity),
-        )
+    pub fn hit_position(&mut self, ball_velocity: Vec2) -> f32 {
+        let difficulty = self.prediction_difficulty(ball_velocity);
+        self.rng
+            .gen_range(self.hit_range.0 - difficulty, self.hit_range.1 + difficulty)
     }
 
-    fn accuracy_variation(&self) -> f32 {
-        gen_range(self.accuracy, 2.0 - self.accuracy)
+    fn accuracy_variation(&mut self) -> f32 {
+        let accuracy = self.effective_accuracy();
+        self.rng.gen_range(accuracy, 2.0 - accuracy)
+    }
+
+    pub fn predict_ball_position(&self, x: f32, ball: &GameObject, bounds: Bounds) -> Vec2 {
+        if ball.angular_velocity.abs() > MIN_CURVING_SPIN && ball.material.magnus != 0.0 {
+            self.predict_curved_position(x, ball, bounds)
+        } else {
+            self.predict_straight_position(x, ball.position, ball.velocity, bounds)
+        }
+    }
+
+    // Steps a copy of the ball with the game's physics until it crosses `x`, so spin, the speed
+    // cap and bounce materials are all accounted for. Gives up if the ball turns away from `x`.
+    pub fn simulate_ball_position(
+        &self,
+        x: f32,
+        ball: &GameObject,
+        obstacles: &[&GameObject],
+    ) -> Option<Vec2> {
+        self.simulate_flight(x, ball, obstacles, |_| {})
+    }
+
+    // Calls `visit` with every position of the ball on its way to `x`.
+    fn simulate_flight(
+        &self,
+        x: f32,
+        ball: &GameObject,
+        obstacles: &[&GameObject],
+        mut visit: impl FnMut(Vec2),
+    ) -> Option<Vec2> {
+        let dt = 1.0 / TICK_RATE;
+        let mut ball = ball.clone();
+        ball.velocity.y *= self.accuracy_variation;
+        let direction = (x - ball.position.x).signum();
+
+        for _ in 0..MAX_PREDICTION_STEPS {
+            let previous = ball.position;
+            ball.handle_bounces(obstacles, dt);
+            visit(previous);
+
+            if (ball.position.x - x) * (previous.x - x) <= 0.0 {
+                let t = (x - previous.x) / (ball.position.x - previous.x);
+                return Some(vec2(x, previous.y + (ball.position.y - previous.y) * t));
+            }
+            if ball.velocity.x * direction <= 0.0 {
+                return None;
+            }
+        }
+        None
     }
 
-    pub fn predict_ball_position(
-        &mut self,
+    pub fn predict_straight_position(
+        &self,
         x: f32,
         ball_position: Vec2,
         ball_velocity: Vec2,
@@ -107,10 +645,57 @@ impl AiLogic {
 
         vec2(x, y.min(2.0 * height - y))
     }
+
+    // Curved trajectories have no closed form, so integrate the flight the same way the physics
+    // step does, reflecting off the top and bottom walls.
+    fn predict_curved_position(&self, x: f32, ball: &GameObject, bounds: Bounds) -> Vec2 {
+        self.reflect_flight(x, ball, ball.angular_velocity, bounds, |_| {})
+            .unwrap_or_else(|| {
+                self.predict_straight_position(x, ball.position, ball.velocity, bounds)
+            })
+    }
+
+    // Calls `visit` with every position of the ball on its way to `x`, starting with `spin`.
+    fn reflect_flight(
+        &self,
+        x: f32,
+        ball: &GameObject,
+        mut spin: f32,
+        bounds: Bounds,
+        mut visit: impl FnMut(Vec2),
+    ) -> Option<Vec2> {
+        let height = bounds.h - BALL_SIZE.1;
+        let dt = 1.0 / TICK_RATE;
+
+        let mut position = ball.position;
+        let mut velocity = vec2(ball.velocity.x, ball.velocity.y * self.accuracy_variation);
+
+        for _ in 0..MAX_PREDICTION_STEPS {
+            velocity += vec2(-velocity.y, velocity.x) * spin * ball.material.magnus * dt;
+            spin *= (1.0 - ball.material.spin_damping * dt).max(0.0);
+
+            visit(position);
+            let next = position + velocity * dt;
+            if (next.x - x) * (position.x - x) <= 0.0 {
+                let t = (x - position.x) / (next.x - position.x);
+                return Some(vec2(x, position.y + (next.y - position.y) * t));
+            }
+            position = next;
+
+            if position.y < bounds.y {
+                position.y = 2.0 * bounds.y - position.y;
+                velocity.y = velocity.y.abs();
+            } else if position.y > height {
+                position.y = 2.0 * height - position.y;
+                velocity.y = -velocity.y.abs();
+            }
+        }
+        None
+    }
 }
 
 pub struct Ai<'a> {
-    pub name: &'a str,
+    pub name: Cow<'a, str>,
     pub logic: AiLogic,
 }
 
@@ -122,8 +707,104 @@ impl<'a> Ai<'a> {
         reaction_time: u16,
     ) -> Self {
         Self {
-            name,
+            name: Cow::Borrowed(name),
             logic: AiLogic::new(hit_range, inaccuracy, reaction_time),
         }
     }
 }
+
+impl Ai<'static> {
+    pub fn from_profile(profile: &AiProfile) -> Self {
+        let mut ai = Self {
+            name: Cow::Owned(profile.name.clone()),
+            logic: AiLogic::new(profile.hit_range, profile.accuracy, profile.reaction_time),
+        };
+        ai.apply_profile(profile);
+        ai
+    }
+}
+
+impl Ai<'_> {
+    // Retunes the AI in place, keeping its random stream and current prediction.
+    pub fn apply_profile(&mut self, profile: &AiProfile) {
+        self.logic.hit_range = profile.hit_range;
+        self.logic.accuracy = profile.accuracy;
+        self.logic.reaction_time = profile.reaction_time;
+        self.logic.max_speed = profile.max_speed;
+        self.logic.anticipation = profile.anticipation;
+        self.logic.aggression = profile.aggression;
+        self.logic.prediction = profile.prediction;
+        self.logic.speed_reaction = profile.speed_reaction;
+        self.logic.fatigue = profile.fatigue;
+        self.logic.overshoot = profile.overshoot;
+        self.logic.hesitation = profile.hesitation;
+    }
+}
+
+impl PaddleController for Ai<'static> {
+    fn name(&self) -> Option<&str> {
+        Some(&self.name)
+    }
+
+    fn seed(&mut self, rng: Rng) {
+        self.logic.rng = rng;
+    }
+
+    fn control(&mut self, context: &ControlContext) -> PaddleIntent {
+        self.logic.observe(context);
+
+        match self.logic.predicted_position {
+            Some(predicted_position) => {
+                let logic = &mut self.logic;
+                if context.now.since(logic.collision_time) >= logic.reaction_delay {
+                    let mut target = logic.target(predicted_position, context.paddle);
+                    // Head past the target until halfway into the overshoot, then come back.
+                    let offset = logic.overshoot_offset;
+                    if offset != 0.0 {
+                        if (context.paddle.position.y - target.y) * offset.signum()
+                            >= offset.abs() / 2.0
+                        {
+                            logic.overshoot_offset = 0.0;
+                        } else {
+                            target.y += offset;
+                        }
+                    }
+                    PaddleIntent::MoveTo {
+                        target,
+                        velocity_scale: logic.max_speed * (1.0 - logic.tiredness() / 2.0),
+                        acceleration_scale: 1.0,
+                    }
+                } else {
+                    PaddleIntent::Idle
+                }
+            }
+            // While the ball is going away, drift back towards the center, or follow the ball
+            // up to the anticipation fraction of the way to get ahead of the return.
+            None => {
+                let center = context.bounds.center();
+                let ball_y = context.ball.collider.center().y;
+                let y = center.y + (ball_y - center.y) * self.logic.anticipation;
+                PaddleIntent::MoveTo {
+                    target: vec2(center.x, y) - Vec2::from((PLAYER_WIDTH, PLAYER_HEIGHT)) / 2.0,
+                    velocity_scale: self.logic.max_speed / 3.0,
+                    acceleration_scale: 1.0,
+                }
+            }
+        }
+    }
+
+    fn ai(&self) -> Option<&Ai<'static>> {
+        Some(self)
+    }
+
+    fn ai_mut(&mut self) -> Option<&mut Ai<'static>> {
+        Some(self)
+    }
+}
+
+// Approximates exp(-x) for x >= 0 with the reciprocal of the first terms of its Taylor series.
+// `exp` may round differently on other platforms, so the simulation sticks to basic arithmetic
+// to keep replays identical everywhere.
+pub fn decay(x: f32) -> f32 {
+    1.0 / (1.0 + x * (1.0 + x / 2.0 * (1.0 + x / 3.0 * (1.0 + x / 4.0))))
+}
@@ -0,0 +1,731 @@
+use macroquad::prelude::{vec2, Vec2};
+use serde::Serialize;
+
+use crate::{
+    bounds::Bounds,
+    clock::{Clock, SimTime},
+    constants::*,
+    controller::{ControlContext, PaddleController, PaddleIntent},
+    events::{GameEvent, Side, Wall},
+    physics::{Collider, ColliderType, GameObject, Material, PhysicsConfig},
+    player::Player,
+    rng::{Rng, BOTTOM_AI_STREAM, LEFT_AI_STREAM, RIGHT_AI_STREAM, SERVE_STREAM, TOP_AI_STREAM},
+    timestep::FixedTimestep,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Edge {
+    Left,
+    Top,
+    Right,
+    Bottom,
+}
+
+// Clockwise, which is also the order of `ArenaState::seats`.
+pub const EDGES: [Edge; 4] = [Edge::Left, Edge::Top, Edge::Right, Edge::Bottom];
+
+impl Edge {
+    fn index(self) -> usize {
+        match self {
+            Edge::Left => 0,
+            Edge::Top => 1,
+            Edge::Right => 2,
+            Edge::Bottom => 3,
+        }
+    }
+
+    pub fn opposite(self) -> Self {
+        EDGES[(self.index() + 2) % 4]
+    }
+
+    pub fn clockwise(self) -> Self {
+        EDGES[(self.index() + 1) % 4]
+    }
+
+    pub fn is_horizontal(self) -> bool {
+        matches!(self, Edge::Top | Edge::Bottom)
+    }
+
+    // The side this edge plays as in its view of the arena, see `View`.
+    pub fn side(self) -> Side {
+        match self {
+            Edge::Left | Edge::Bottom => Side::Left,
+            Edge::Right | Edge::Top => Side::Right,
+        }
+    }
+
+    // Points from the center of the arena towards the edge.
+    fn direction(self) -> Vec2 {
+        match self {
+            Edge::Left => vec2(-1.0, 0.0),
+            Edge::Top => vec2(0.0, -1.0),
+            Edge::Right => vec2(1.0, 0.0),
+            Edge::Bottom => vec2(0.0, 1.0),
+        }
+    }
+
+    // The left and right players keep their names from two-player games.
+    pub fn default_name(self) -> &'static str {
+        match self {
+            Edge::Left => "Player 1",
+            Edge::Right => "Player 2",
+            Edge::Top => "Player 3",
+            Edge::Bottom => "Player 4",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ArenaEvent {
+    // `offset` goes from -1.0 to 1.0 along the paddle, top to bottom or left to right.
+    PaddleHit { edge: Edge, offset: f32, speed: f32 },
+    // The ball bounced off the edge of an eliminated player.
+    WallBounce { edge: Edge },
+    LifeLost { edge: Edge },
+    Eliminated { edge: Edge },
+    Won { winner: Edge },
+}
+
+pub struct Seat {
+    pub edge: Edge,
+    pub player: Player,
+    pub controller: Box<dyn PaddleController>,
+    pub lives: u32,
+    // Behind the paddle: a sensor taking a life when the ball passes, and a plain wall once the
+    // player is out.
+    pub goal: GameObject,
+}
+
+impl Seat {
+    pub fn is_out(&self) -> bool {
+        self.lives == 0
+    }
+
+    // The paddle, or the wall that replaced it.
+    fn guard(&self) -> &GameObject {
+        if self.is_out() {
+            &self.goal
+        } else {
+            &self.player.object
+        }
+    }
+}
+
+// Four players guarding the four edges of the arena, each losing a life when the ball gets past
+// them, until one is left.
+pub struct ArenaState {
+    pub ball: GameObject,
+    pub seats: Vec<Seat>,
+
+    pub timestep: FixedTimestep,
+    pub clock: Clock,
+    pub serve_time: Option<SimTime>,
+
+    pub lives: u32,
+    // Who the next serve heads to.
+    pub receiver: Edge,
+    pub winner: Option<Edge>,
+
+    pub seed: u64,
+    pub serve_rng: Rng,
+
+    pub events: Vec<ArenaEvent>,
+}
+
+impl ArenaState {
+    pub fn new(controllers: [Box<dyn PaddleController>; 4], lives: u32, seed: u64) -> Self {
+        let seats = EDGES
+            .into_iter()
+            .zip(controllers)
+            .map(|(edge, controller)| Seat {
+                edge,
+                player: paddle(edge),
+                controller,
+                lives,
+                goal: goal(edge),
+            })
+            .collect();
+
+        let mut ball = GameObject::from_pos(
+            BOUNDS.center().x - BALL_RADIUS,
+            BOUNDS.center().y - BALL_RADIUS,
+            ColliderType::Circle(BALL_RADIUS),
+        );
+        ball.material = Material::BALL;
+
+        let mut serve_rng = Rng::new(seed, SERVE_STREAM);
+        let receiver = EDGES[(serve_rng.next_u32() % 4) as usize];
+
+        let mut state = Self {
+            ball,
+            seats,
+
+            timestep: FixedTimestep::new(TICK_RATE),
+            clock: Clock::new(),
+            serve_time: Some(SimTime::ZERO),
+
+            lives,
+            receiver,
+            winner: None,
+
+            seed,
+            serve_rng,
+
+            events: Vec::new(),
+        };
+        for edge in EDGES {
+            state.seat_controller(edge);
+        }
+        state
+    }
+}
+
+// Top and bottom paddles are the side paddles laid flat, moving sideways.
+fn paddle(edge: Edge) -> Player {
+    let (x, y) = match edge {
+        Edge::Left => (
+            BOUNDS.x + PLAYER_PADDING,
+            BOUNDS.center().y - PLAYER_HEIGHT / 2.0,
+        ),
+        Edge::Right => (
+            BOUNDS.w - PLAYER_PADDING - PLAYER_WIDTH,
+            BOUNDS.center().y - PLAYER_HEIGHT / 2.0,
+        ),
+        Edge::Top => (
+            BOUNDS.center().x - PLAYER_HEIGHT / 2.0,
+            BOUNDS.y + PLAYER_PADDING,
+        ),
+        Edge::Bottom => (
+            BOUNDS.center().x - PLAYER_HEIGHT / 2.0,
+            BOUNDS.h - PLAYER_PADDING - PLAYER_WIDTH,
+        ),
+    };
+    let flat = |(a, b): (f32, f32)| {
+        if edge.is_horizontal() {
+            vec2(b, a)
+        } else {
+            vec2(a, b)
+        }
+    };
+
+    let size = flat((PLAYER_WIDTH, PLAYER_HEIGHT));
+    let object = GameObject::from_pos(x, y, ColliderType::Rectangle(size.x, size.y));
+    Player::new(
+        edge.default_name(),
+        object,
+        BOUNDS,
+        flat(PLAYER_VELOCITY),
+        flat(PLAYER_ACCELERATION),
+    )
+}
+
+fn goal(edge: Edge) -> GameObject {
+    let (x, y, w, h) = match edge {
+        Edge::Left => (
+            BOUNDS.x - BOUNDS_THICKNESS,
+            BOUNDS.y,
+            BOUNDS_THICKNESS,
+            BOUNDS.h,
+        ),
+        Edge::Right => (BOUNDS.w, BOUNDS.y, BOUNDS_THICKNESS, BOUNDS.h),
+        Edge::Top => (
+            BOUNDS.x,
+            BOUNDS.y - BOUNDS_THICKNESS,
+            BOUNDS.w,
+            BOUNDS_THICKNESS,
+        ),
+        Edge::Bottom => (BOUNDS.x, BOUNDS.h, BOUNDS.w, BOUNDS_THICKNESS),
+    };
+    let mut goal = GameObject::from_pos(x, y, ColliderType::Rectangle(w, h));
+    goal.is_sensor = true;
+    goal
+}
+
+impl ArenaState {
+    pub fn advance(&mut self, frame_time: f32) {
+        self.events.clear();
+
+        let sim_time = self.clock.scaled(frame_time);
+        for _ in 0..self.timestep.accumulate(sim_time) {
+            let events = self.step(self.timestep.tick);
+            self.events.extend(events);
+        }
+    }
+
+    pub fn alpha(&self) -> f32 {
+        self.timestep.alpha()
+    }
+
+    pub fn reset_ball(&mut self) {
+        if self.winner.is_none() {
+            self.serve_time = Some(self.clock.now());
+        }
+    }
+
+    // Gives everyone their lives back and serves again.
+    pub fn rematch(&mut self) {
+        for seat in &mut self.seats {
+            seat.lives = self.lives;
+            seat.goal.is_sensor = true;
+        }
+        self.winner = None;
+        self.receiver = self.receiver.clockwise();
+        self.serve_time = Some(self.clock.now());
+    }
+
+    pub fn apply_physics(&mut self, config: &PhysicsConfig) {
+        self.ball.material = config.ball;
+        for seat in &mut self.seats {
+            seat.player.object.material = config.paddle;
+            seat.goal.material = config.wall;
+        }
+    }
+
+    pub fn seat(&self, edge: Edge) -> &Seat {
+        &self.seats[edge.index()]
+    }
+
+    pub fn seat_mut(&mut self, edge: Edge) -> &mut Seat {
+        &mut self.seats[edge.index()]
+    }
+
+    pub fn set_controller(&mut self, edge: Edge, controller: Box<dyn PaddleController>) {
+        self.seat_mut(edge).controller = controller;
+        self.seat_controller(edge);
+    }
+
+    fn seat_controller(&mut self, edge: Edge) {
+        let stream = match edge {
+            Edge::Left => LEFT_AI_STREAM,
+            Edge::Top => TOP_AI_STREAM,
+            Edge::Right => RIGHT_AI_STREAM,
+            Edge::Bottom => BOTTOM_AI_STREAM,
+        };
+        let seed = self.seed;
+        let seat = self.seat_mut(edge);
+        seat.controller.seed(Rng::new(seed, stream));
+        seat.player.name = seat
+            .controller
+            .name()
+            .unwrap_or(edge.default_name())
+            .to_owned();
+    }
+
+    pub fn poll_controllers(&mut self) {
+        // Pointers are read on the arena as drawn, then turned with the view of each seat.
+        for seat in &mut self.seats {
+            let view = View::of(seat.edge);
+            let bounds = seat.player.bounds;
+            seat.controller.poll(view.bounds(), &|position| {
+                view.point(bounds.convert_to_local(position))
+            });
+        }
+    }
+
+    pub fn remaining(&self) -> Vec<Edge> {
+        EDGES
+            .into_iter()
+            .filter(|&edge| !self.seat(edge).is_out())
+            .collect()
+    }
+
+    pub fn step(&mut self, dt: f32) -> Vec<ArenaEvent> {
+        self.clock.tick(dt);
+        let now = self.clock.now();
+
+        self.ball.store_previous_position();
+        for seat in &mut self.seats {
+            seat.player.object.store_previous_position();
+        }
+
+        if let Some(serve_time) = self.serve_time {
+            self.ball.position = BOUNDS.center() - Vec2::from(BALL_SIZE) / 2.0;
+            self.ball.velocity = vec2(0.0, 0.0);
+            self.ball.angular_velocity = 0.0;
+            self.ball.store_previous_position();
+
+            if now > serve_time + SERVE_DELAY {
+                let direction = self.receiver.direction();
+                let across = vec2(-direction.y, direction.x);
+                self.ball.velocity =
+                    direction * SERVE_SPEED + across * self.serve_rng.gen_range(-400.0, 400.0);
+                self.serve_time = None;
+            }
+        }
+
+        //___PHYSICS___//
+
+        // Eliminated players leave their paddle behind, the wall on their edge takes over.
+        let mut objects = Vec::new();
+        let mut struck = Vec::new();
+        for seat in &self.seats {
+            if !seat.is_out() {
+                objects.push(&seat.player.object);
+                struck.push(Struck::Paddle(seat.edge));
+            }
+        }
+        for seat in &self.seats {
+            objects.push(&seat.goal);
+            struck.push(Struck::Goal(seat.edge));
+        }
+
+        let collisions = self.ball.handle_bounces(&objects, dt);
+
+        let mut events: Vec<ArenaEvent> = collisions
+            .iter()
+            .map(|collision| {
+                let center = objects[collision.index].collider.center();
+                let size = objects[collision.index].collider.rect.size();
+                match struck[collision.index] {
+                    Struck::Paddle(edge) => {
+                        let point = collision.contact.point;
+                        let offset = if edge.is_horizontal() {
+                            (point.x - center.x) / (size.x / 2.0)
+                        } else {
+                            (point.y - center.y) / (size.y / 2.0)
+                        };
+                        ArenaEvent::PaddleHit {
+                            edge,
+                            offset,
+                            speed: collision.velocity.length(),
+                        }
+                    }
+                    Struck::Goal(edge) if self.seat(edge).is_out() => {
+                        ArenaEvent::WallBounce { edge }
+                    }
+                    Struck::Goal(edge) => ArenaEvent::LifeLost { edge },
+                }
+            })
+            .collect();
+
+        //___LIVES___//
+
+        // The ball is served again after the first life lost, even if it crossed a corner.
+        let lost = events.iter().find_map(|event| match *event {
+            ArenaEvent::LifeLost { edge } => Some(edge),
+            _ => None,
+        });
+        events
+            .retain(|event| !matches!(event, ArenaEvent::LifeLost { edge } if Some(*edge) != lost));
+        if let Some(edge) = lost {
+            if self.winner.is_none() {
+                self.lose_life(edge, now, &mut events);
+            }
+        }
+
+        //___PLAYERS___//
+
+        for edge in EDGES {
+            if !self.seat(edge).is_out() {
+                self.control_seat(edge, &events, now, dt);
+            }
+        }
+
+        events
+    }
+
+    fn lose_life(&mut self, edge: Edge, now: SimTime, events: &mut Vec<ArenaEvent>) {
+        let seat = self.seat_mut(edge);
+        seat.lives = seat.lives.saturating_sub(1);
+        if seat.is_out() {
+            seat.goal.is_sensor = false;
+            events.push(ArenaEvent::Eliminated { edge });
+        }
+
+        let remaining = self.remaining();
+        if let [winner] = remaining[..] {
+            // The ball waits in the middle until a rematch, like at the end of a match.
+            events.push(ArenaEvent::Won { winner });
+            self.winner = Some(winner);
+            self.serve_time = None;
+            self.ball.position = BOUNDS.center() - Vec2::from(BALL_SIZE) / 2.0;
+            self.ball.velocity = vec2(0.0, 0.0);
+            self.ball.angular_velocity = 0.0;
+            self.ball.collider.update_pos(self.ball.position);
+            return;
+        }
+
+        // Whoever lost the life receives the next serve, or the next player still in.
+        let mut receiver = edge;
+        while self.seat(receiver).is_out() {
+            receiver = receiver.clockwise();
+        }
+        self.receiver = receiver;
+        self.serve_time = Some(now);
+    }
+
+    fn control_seat(&mut self, edge: Edge, events: &[ArenaEvent], now: SimTime, dt: f32) {
+        let view = View::of(edge);
+        let paddle = view.object(&self.seat(edge).player.object);
+        let opponent = view.object(self.seat(edge.opposite()).guard());
+        let walls = [
+            view.object(self.seat(edge.clockwise()).guard()),
+            view.object(self.seat(edge.opposite().clockwise()).guard()),
+        ];
+        let ball = view.object(&self.ball);
+        let speed = self.ball.velocity.length();
+        let events: Vec<GameEvent> = events
+            .iter()
+            .filter_map(|event| view.event(edge, event, speed))
+            .collect();
+
+        let seat = self.seat_mut(edge);
+        let intent = seat.controller.control(&ControlContext {
+            side: edge.side(),
+            paddle: &paddle,
+            opponent: &opponent,
+            ball: &ball,
+            walls: &[&walls[0], &walls[1]],
+            events: &events,
+            bounds: view.bounds(),
+            now,
+        });
+        seat.player.apply_intent(view.intent(intent, &paddle), dt);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Struck {
+    Paddle(Edge),
+    Goal(Edge),
+}
+
+// How one seat sees the arena. Controllers and the AI only know paddles guarding the left and
+// right edges, so the arena is turned a quarter for the top and bottom paddles, counterclockwise
+// so the bottom paddle plays on the left and the top one on the right. Turning keeps the spin of
+// the ball, where mirroring would flip it.
+#[derive(Debug, Clone, Copy)]
+struct View {
+    turned: bool,
+}
+
+impl View {
+    fn of(edge: Edge) -> Self {
+        Self {
+            turned: edge.is_horizontal(),
+        }
+    }
+}
+
+impl View {
+    fn bounds(self) -> Bounds {
+        if self.turned {
+            Bounds::new(BOUNDS.y, BOUNDS.x, BOUNDS.h, BOUNDS.w)
+        } else {
+            BOUNDS
+        }
+    }
+
+    fn point(self, point: Vec2) -> Vec2 {
+        if self.turned {
+            vec2(BOUNDS.h - point.y, point.x)
+        } else {
+            point
+        }
+    }
+
+    fn point_back(self, point: Vec2) -> Vec2 {
+        if self.turned {
+            vec2(point.y, BOUNDS.h - point.x)
+        } else {
+            point
+        }
+    }
+
+    fn vector(self, vector: Vec2) -> Vec2 {
+        if self.turned {
+            vec2(-vector.y, vector.x)
+        } else {
+            vector
+        }
+    }
+
+    fn size(self, size: Vec2) -> Vec2 {
+        if self.turned {
+            vec2(size.y, size.x)
+        } else {
+            size
+        }
+    }
+
+    // Objects are placed by their top left corner, which turning moves to another corner.
+    fn corner(self, corner: Vec2, size: Vec2) -> Vec2 {
+        self.point(corner + size / 2.0) - self.size(size) / 2.0
+    }
+
+    fn corner_back(self, corner: Vec2, size: Vec2) -> Vec2 {
+        self.point_back(corner + size / 2.0) - self.size(size) / 2.0
+    }
+
+    fn object(self, object: &GameObject) -> GameObject {
+        if !self.turned {
+            return object.clone();
+        }
+        let size = object.collider.rect.size();
+        let position = self.corner(object.position, size);
+        let shape = match object.collider.shape {
+            ColliderType::Rectangle(w, h) => ColliderType::Rectangle(h, w),
+            ColliderType::Circle(radius) => ColliderType::Circle(radius),
+        };
+
+        let mut turned = object.clone();
+        turned.position = position;
+        turned.previous_position = self.corner(object.previous_position, size);
+        turned.velocity = self.vector(object.velocity);
+        turned.acceleration = self.vector(object.acceleration);
+        turned.collider = Collider::new(position.x, position.y, shape);
+        turned
+    }
+
+    // Turns back the target of a paddle seen in this view.
+    fn intent(self, intent: PaddleIntent, paddle: &GameObject) -> PaddleIntent {
+        match intent {
+            PaddleIntent::MoveTo {
+                target,
+                velocity_scale,
+                acceleration_scale,
+            } => PaddleIntent::MoveTo {
+                target: self.corner_back(target, paddle.collider.rect.size()),
+                velocity_scale,
+                acceleration_scale,
+            },
+            PaddleIntent::Idle => PaddleIntent::Idle,
+        }
+    }
+
+    // What `edge` sees of an arena event, as if it were playing a two-player game: every other
+    // paddle, and the wall opposite, returns the ball like an opponent would.
+    fn event(self, edge: Edge, event: &ArenaEvent, speed: f32) -> Option<GameEvent> {
+        let side = edge.side();
+        match *event {
+            ArenaEvent::PaddleHit {
+                edge: hitter,
+                offset,
+                speed,
+            } => Some(GameEvent::PaddleHit {
+                player: if hitter == edge {
+                    side
+                } else {
+                    side.opponent()
+                },
+                offset,
+                speed,
+            }),
+            ArenaEvent::WallBounce { edge: wall } if wall == edge.opposite() => {
+                Some(GameEvent::PaddleHit {
+                    player: side.opponent(),
+                    offset: 0.0,
+                    speed,
+                })
+            }
+            ArenaEvent::WallBounce { edge: wall } => {
+                let top =
+                    self.point(wall.direction() + BOUNDS.center()).y < self.bounds().center().y;
+                Some(GameEvent::WallBounce {
+                    wall: if top { Wall::Top } else { Wall::Bottom },
+                })
+            }
+            ArenaEvent::LifeLost { edge: loser } => Some(GameEvent::Goal {
+                scorer: if loser == edge { side.opponent() } else { side },
+            }),
+            ArenaEvent::Eliminated { .. } | ArenaEvent::Won { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::RemoteController;
+
+    // Paddles whose senders are dropped right away, so they stay in the middle of their edge.
+    fn idle_arena(lives: u32) -> ArenaState {
+        let controllers = EDGES.map(|edge| {
+            Box::new(RemoteController::new(edge.default_name()).0) as Box<dyn PaddleController>
+        });
+        ArenaState::new(controllers, lives, 1)
+    }
+
+    // Sends the ball from `center` past the idle paddles, returning the events up to and including
+    // the first tick with any.
+    fn shoot(arena: &mut ArenaState, center: Vec2, velocity: Vec2) -> Vec<ArenaEvent> {
+        arena.serve_time = None;
+        arena.ball.position = center - Vec2::from(BALL_SIZE) / 2.0;
+        arena.ball.velocity = velocity;
+        arena.ball.angular_velocity = 0.0;
+        arena.ball.collider.update_pos(arena.ball.position);
+        let dt = arena.timestep.tick;
+        (0..(2.0 / dt) as usize)
+            .map(|_| arena.step(dt))
+            .find(|events| !events.is_empty())
+            .expect("the ball reaches an edge")
+    }
+
+    #[test]
+    fn ball_past_a_paddle_takes_a_life() {
+        let mut arena = idle_arena(3);
+        let events = shoot(&mut arena, vec2(100.0, 100.0), vec2(-1000.0, 0.0));
+
+        assert!(matches!(
+            events[..],
+            [ArenaEvent::LifeLost { edge: Edge::Left }]
+        ));
+        assert_eq!(arena.seat(Edge::Left).lives, 2);
+        assert!(EDGES[1..].iter().all(|&edge| arena.seat(edge).lives == 3));
+        assert_eq!(arena.receiver, Edge::Left);
+        assert!(arena.serve_time.is_some());
+    }
+
+    #[test]
+    fn eliminated_edge_turns_into_a_wall() {
+        let mut arena = idle_arena(1);
+        let events = shoot(&mut arena, vec2(100.0, 100.0), vec2(-1000.0, 0.0));
+        assert!(matches!(
+            events[..],
+            [
+                ArenaEvent::LifeLost { edge: Edge::Left },
+                ArenaEvent::Eliminated { edge: Edge::Left }
+            ]
+        ));
+        assert!(arena.seat(Edge::Left).is_out());
+
+        let events = shoot(&mut arena, vec2(100.0, 100.0), vec2(-1000.0, 0.0));
+        assert!(matches!(
+            events[..],
+            [ArenaEvent::WallBounce { edge: Edge::Left }]
+        ));
+        assert!(arena.ball.velocity.x > 0.0);
+        assert!(arena.serve_time.is_none());
+        assert_eq!(arena.seat(Edge::Left).lives, 0);
+    }
+
+    #[test]
+    fn last_edge_standing_wins() {
+        let mut arena = idle_arena(1);
+        shoot(&mut arena, vec2(100.0, 100.0), vec2(-1000.0, 0.0));
+        shoot(&mut arena, vec2(100.0, 100.0), vec2(0.0, -1000.0));
+        assert_eq!(arena.remaining(), [Edge::Right, Edge::Bottom]);
+        assert!(arena.winner.is_none());
+
+        let events = shoot(&mut arena, vec2(1100.0, 100.0), vec2(1000.0, 0.0));
+        assert!(matches!(
+            events[..],
+            [
+                ArenaEvent::LifeLost { edge: Edge::Right },
+                ArenaEvent::Eliminated { edge: Edge::Right },
+                ArenaEvent::Won {
+                    winner: Edge::Bottom
+                }
+            ]
+        ));
+        assert_eq!(arena.winner, Some(Edge::Bottom));
+        assert!(arena.serve_time.is_none());
+
+        // The ball waits in the middle until a rematch.
+        let dt = arena.timestep.tick;
+        for _ in 0..(5.0 / dt) as usize {
+            assert!(arena.step(dt).is_empty());
+        }
+        assert_eq!(arena.ball.velocity, vec2(0.0, 0.0));
+    }
+}
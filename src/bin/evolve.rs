@@ -0,0 +1,173 @@
+use std::process;
+
+use pong_clone::{
+    ai::{AiProfile, AiProfiles},
+    constants::{AI_PROFILES_PATH, PHYSICS_CONFIG_PATH},
+    evolution::{Evolution, EvolutionSettings, Genome, GENES},
+    physics::PhysicsConfig,
+};
+
+struct Options {
+    profiles: String,
+    physics: String,
+    references: Option<Vec<String>>,
+    name: String,
+    generations: u32,
+    seed: u64,
+    settings: EvolutionSettings,
+}
+
+fn main() {
+    let mut options = parse_args().unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        eprintln!(
+            "usage: evolve [--profiles PATH] [--physics PATH] [--reference NAME,...] [--name NAME] [--generations N] \
+             [--population N] [--games N] [--target WIN_RATE] [--points N] [--max-time SECS] \
+             [--mutation-rate X] [--seed N]"
+        );
+        process::exit(2);
+    });
+
+    let profiles = AiProfiles::load(&options.profiles).unwrap_or_else(|error| exit(error));
+    options.settings.matches.physics =
+        PhysicsConfig::load(&options.physics).unwrap_or_else(|error| exit(error));
+    let references: Vec<AiProfile> = match &options.references {
+        Some(names) => names
+            .iter()
+            .map(|name| {
+                profiles
+                    .get(name)
+                    .cloned()
+                    .unwrap_or_else(|| exit(format!("no profile named {:?}", name)))
+            })
+            .collect(),
+        None => profiles.profiles.clone(),
+    };
+    if profiles.get(&options.name).is_some() {
+        exit(format!(
+            "{} already has a profile named {:?}, pick another --name",
+            options.profiles, options.name
+        ));
+    }
+
+    // The other hand-made profiles give the search a head start. The references themselves are
+    // left out, a copy of the reference bot trivially scores a 50% win rate against it.
+    let seeds: Vec<Genome> = profiles
+        .profiles
+        .iter()
+        .filter(|profile| profile.is_tunable())
+        .filter(|profile| {
+            !references
+                .iter()
+                .any(|reference| reference.name == profile.name)
+        })
+        .map(Genome::from_profile)
+        .collect();
+
+    println!(
+        "Evolving {} genomes for {} generations against {}, targeting a {:.0}% win rate",
+        options.settings.population,
+        options.generations,
+        references
+            .iter()
+            .map(|profile| profile.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", "),
+        options.settings.target_win_rate * 100.0
+    );
+
+    let mut evolution = Evolution::new(options.settings.clone(), references, &seeds, options.seed);
+    report(&evolution);
+    for _ in 0..options.generations {
+        evolution.next_generation();
+        report(&evolution);
+    }
+
+    let (genome, evaluation) = evolution.best();
+    let profile = genome.to_profile(&options.name);
+    println!(
+        "\nBest genome: win rate {:.1}%, point share {:.1}%",
+        evaluation.win_rate * 100.0,
+        evaluation.point_share * 100.0
+    );
+    for ((gene, _, _), value) in GENES.iter().zip(genome.0) {
+        println!("  {:<16} {:.3}", gene, value);
+    }
+
+    profile
+        .append_to(&options.profiles)
+        .unwrap_or_else(|error| exit(error));
+    println!("Added profile {:?} to {}", options.name, options.profiles);
+}
+
+fn report(evolution: &Evolution) {
+    let (_, best) = evolution.best();
+    println!(
+        "generation {:>4}  best fitness {:>7.3}  mean fitness {:>7.3}  best win rate {:>5.1}%",
+        evolution.generation,
+        best.fitness,
+        evolution.mean_fitness(),
+        best.win_rate * 100.0
+    );
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        profiles: AI_PROFILES_PATH.to_owned(),
+        physics: PHYSICS_CONFIG_PATH.to_owned(),
+        references: None,
+        name: "Evolved".to_owned(),
+        generations: 20,
+        seed: 0,
+        settings: EvolutionSettings::default(),
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or(format!("{} expects a value", arg))?;
+        match arg.as_str() {
+            "--profiles" => options.profiles = value,
+            "--physics" => options.physics = value,
+            "--reference" => {
+                options.references = Some(
+                    value
+                        .split(',')
+                        .map(|name| name.trim().to_owned())
+                        .collect(),
+                )
+            }
+            "--name" => options.name = value,
+            "--generations" => options.generations = parse(&arg, value)?,
+            "--seed" => options.seed = parse(&arg, value)?,
+            "--population" => options.settings.population = parse(&arg, value)?,
+            "--games" => options.settings.games = parse(&arg, value)?,
+            "--target" => options.settings.target_win_rate = parse(&arg, value)?,
+            "--points" => options.settings.matches.points = parse(&arg, value)?,
+            "--max-time" => options.settings.matches.max_time = parse(&arg, value)?,
+            "--mutation-rate" => options.settings.mutation_rate = parse(&arg, value)?,
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+
+    if options.settings.population < 2 {
+        return Err("--population must be at least 2".to_owned());
+    }
+    if options.settings.matches.points == 0 {
+        return Err("--points must be at least 1".to_owned());
+    }
+    if !(0.0..=1.0).contains(&options.settings.target_win_rate) {
+        return Err("--target must be between 0 and 1".to_owned());
+    }
+    Ok(options)
+}
+
+fn parse<T: std::str::FromStr>(arg: &str, value: String) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} got an invalid value {:?}", arg, value))
+}
+
+fn exit(error: impl std::fmt::Display) -> ! {
+    eprintln!("{}", error);
+    process::exit(1);
+}
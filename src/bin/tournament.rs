@@ -0,0 +1,131 @@
+use std::{fs, process};
+
+use pong_clone::{
+    ai::AiProfiles,
+    constants::{AI_PROFILES_PATH, PHYSICS_CONFIG_PATH},
+    physics::PhysicsConfig,
+    tournament::{MatchSettings, Tournament},
+};
+
+struct Options {
+    profiles: String,
+    physics: String,
+    games: u32,
+    seed: u64,
+    settings: MatchSettings,
+    json: String,
+}
+
+fn main() {
+    let mut options = parse_args().unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        eprintln!(
+            "usage: tournament [--profiles PATH] [--physics PATH] [--games N] [--seed N] [--points N] [--max-time SECS] [--json PATH]"
+        );
+        process::exit(2);
+    });
+
+    let profiles = AiProfiles::load(&options.profiles).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    if profiles.profiles.len() < 2 {
+        eprintln!(
+            "{}: a tournament needs at least two profiles",
+            options.profiles
+        );
+        process::exit(1);
+    }
+    options.settings.physics = PhysicsConfig::load(&options.physics).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+
+    let tournament = Tournament::round_robin(
+        &profiles.profiles,
+        options.games,
+        options.seed,
+        options.settings,
+    );
+
+    print_table(&tournament);
+
+    let json = serde_json::to_string_pretty(&tournament).expect("tournament results serialize");
+    if let Err(error) = fs::write(&options.json, json) {
+        eprintln!("could not write {}: {}", options.json, error);
+        process::exit(1);
+    }
+    println!("\nFull results written to {}", options.json);
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        profiles: AI_PROFILES_PATH.to_owned(),
+        physics: PHYSICS_CONFIG_PATH.to_owned(),
+        games: 10,
+        seed: 0,
+        settings: MatchSettings::default(),
+        json: "tournament.json".to_owned(),
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} expects a value", arg));
+        match arg.as_str() {
+            "--profiles" => options.profiles = value()?,
+            "--physics" => options.physics = value()?,
+            "--json" => options.json = value()?,
+            "--games" => options.games = parse(&arg, value()?)?,
+            "--seed" => options.seed = parse(&arg, value()?)?,
+            "--points" => options.settings.points = parse(&arg, value()?)?,
+            "--max-time" => options.settings.max_time = parse(&arg, value()?)?,
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+
+    if options.settings.points == 0 {
+        return Err("--points must be at least 1".to_owned());
+    }
+    Ok(options)
+}
+
+fn parse<T: std::str::FromStr>(arg: &str, value: String) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} got an invalid value {:?}", arg, value))
+}
+
+fn print_table(tournament: &Tournament) {
+    println!(
+        "{} games per pairing and side, first to {} points, {}s limit, seed {}\n",
+        tournament.games, tournament.points, tournament.max_time, tournament.seed
+    );
+    println!(
+        "{:<16} {:>6} {:>5} {:>5} {:>5} {:>8} {:>9} {:>11} {:>7} {:>7}",
+        "AI",
+        "Played",
+        "Won",
+        "Drawn",
+        "Lost",
+        "Win rate",
+        "Points",
+        "Avg. rally",
+        "Longest",
+        "Elo"
+    );
+    for standing in &tournament.standings {
+        println!(
+            "{:<16} {:>6} {:>5} {:>5} {:>5} {:>7.1}% {:>9} {:>11.1} {:>7} {:>7.0}",
+            standing.name,
+            standing.played,
+            standing.wins,
+            standing.draws,
+            standing.losses,
+            standing.win_rate() * 100.0,
+            format!("{}-{}", standing.points_for, standing.points_against),
+            standing.average_rally(),
+            standing.longest_rally,
+            standing.elo,
+        );
+    }
+}
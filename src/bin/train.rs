@@ -0,0 +1,256 @@
+use std::{fs, process};
+
+use pong_clone::{
+    ai::{AiProfile, AiProfiles, PredictionMode},
+    constants::{AI_PROFILES_PATH, PHYSICS_CONFIG_PATH},
+    env::{EnvConfig, PongEnv},
+    learning::{Action, Feature, QPolicy},
+    physics::PhysicsConfig,
+    rng::{Rng, TRAINING_STREAM},
+};
+
+struct Options {
+    profiles: String,
+    physics: String,
+    opponent: Option<String>,
+    name: String,
+    policy: Option<String>,
+    resume: bool,
+    episodes: u32,
+    seed: u64,
+    frame_skip: u32,
+    bins: u8,
+    features: Vec<Feature>,
+    learning_rate: f32,
+    discount: f32,
+    epsilon: f32,
+    min_epsilon: f32,
+}
+
+fn main() {
+    let options = parse_args().unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        eprintln!(
+            "usage: train [--profiles PATH] [--physics PATH] [--opponent NAME] [--name NAME] [--policy PATH] [--resume] \
+             [--episodes N] [--seed N] [--frame-skip N] [--bins N] [--features a,b,...] \
+             [--learning-rate X] [--discount X] [--epsilon X] [--min-epsilon X]"
+        );
+        process::exit(2);
+    });
+
+    let profiles = AiProfiles::load(&options.profiles).unwrap_or_else(|error| exit(error));
+    let opponent = match &options.opponent {
+        Some(name) => profiles
+            .get(name)
+            .unwrap_or_else(|| exit(format!("no profile named {:?}", name))),
+        None => &profiles.profiles[0],
+    };
+
+    let policy_path = options.policy.clone().unwrap_or(format!(
+        "config/policies/{}.toml",
+        options.name.to_lowercase()
+    ));
+    let mut policy = if options.resume {
+        QPolicy::load(&policy_path).unwrap_or_else(|error| exit(error))
+    } else {
+        QPolicy::new(&options.features, options.bins, options.frame_skip)
+            .unwrap_or_else(|error| exit(error))
+    };
+
+    let mut config = EnvConfig::new(opponent.clone());
+    config.features = policy.features.clone();
+    config.frame_skip = policy.frame_skip;
+    config.physics = PhysicsConfig::load(&options.physics).unwrap_or_else(|error| exit(error));
+    let mut env = PongEnv::new(config, options.seed);
+
+    println!(
+        "Training {} against {} for {} episodes",
+        options.name, opponent.name, options.episodes
+    );
+    train(&mut env, &mut policy, &options);
+
+    if let Some(directory) = std::path::Path::new(&policy_path).parent() {
+        if let Err(error) = fs::create_dir_all(directory) {
+            exit(format!(
+                "could not create {}: {}",
+                directory.display(),
+                error
+            ));
+        }
+    }
+    policy
+        .save(&policy_path)
+        .unwrap_or_else(|error| exit(error));
+    println!("Policy saved to {}", policy_path);
+
+    match install_profile(&options.profiles, &profiles, &options.name, &policy_path) {
+        Ok(true) => println!("Added profile {:?} to {}", options.name, options.profiles),
+        Ok(false) => println!(
+            "Profile {:?} already exists in {}, it will use the new policy",
+            options.name, options.profiles
+        ),
+        Err(error) => exit(error),
+    }
+
+    // Make sure the saved profile loads back the same way the game will load it.
+    let reloaded = AiProfiles::load(&options.profiles).unwrap_or_else(|error| exit(error));
+    if reloaded
+        .get(&options.name)
+        .and_then(|profile| profile.learned.as_ref())
+        .is_none()
+    {
+        eprintln!(
+            "warning: profile {:?} in {} does not use {}",
+            options.name, options.profiles, policy_path
+        );
+    }
+}
+
+fn train(env: &mut PongEnv, policy: &mut QPolicy, options: &Options) {
+    let mut rng = Rng::new(options.seed, TRAINING_STREAM);
+    let report_every = (options.episodes / 10).max(1);
+    let mut total_reward = 0.0;
+    let mut points_won = 0;
+
+    for episode in 0..options.episodes {
+        let progress = episode as f32 / options.episodes.max(1) as f32;
+        let epsilon = options.epsilon + (options.min_epsilon - options.epsilon) * progress;
+
+        let mut state = policy.state(&env.reset(options.seed.wrapping_add(episode as u64)));
+        loop {
+            let action = if rng.next_f32() < epsilon {
+                Action::ALL[(rng.next_u32() % 3) as usize]
+            } else {
+                policy.best_action(state)
+            };
+
+            let (observation, reward, done) = env.step(action);
+            let next_state = policy.state(&observation);
+            policy.update(
+                state,
+                action,
+                reward,
+                (!done).then_some(next_state),
+                options.learning_rate,
+                options.discount,
+            );
+
+            total_reward += reward;
+            state = next_state;
+            if done {
+                break;
+            }
+        }
+        if env.state.player(env.config.side).score > 0 {
+            points_won += 1;
+        }
+
+        if (episode + 1) % report_every == 0 {
+            println!(
+                "episode {:>6}  epsilon {:.2}  average reward {:>6.2}  points won {:>5.1}%  states {}",
+                episode + 1,
+                epsilon,
+                total_reward / report_every as f32,
+                points_won as f32 * 100.0 / report_every as f32,
+                policy.values.len()
+            );
+            total_reward = 0.0;
+            points_won = 0;
+        }
+    }
+}
+
+// Appends a profile using the policy to the profiles file, unless one with that name exists.
+fn install_profile(
+    path: &str,
+    profiles: &AiProfiles,
+    name: &str,
+    policy_path: &str,
+) -> Result<bool, String> {
+    if profiles.get(name).is_some() {
+        return Ok(false);
+    }
+
+    let profile = AiProfile {
+        name: name.to_owned(),
+        hit_range: (0.0, 1.0),
+        accuracy: 1.0,
+        reaction_time: 0,
+        max_speed: 1.0,
+        anticipation: 0.0,
+        aggression: 0.0,
+        prediction: PredictionMode::default(),
+        speed_reaction: 0.0,
+        fatigue: 0.0,
+        overshoot: 0.0,
+        hesitation: 0,
+        policy: Some(policy_path.to_owned()),
+        script: None,
+        learned: None,
+    };
+    profile.append_to(path).map_err(|error| error.to_string())?;
+    Ok(true)
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        profiles: AI_PROFILES_PATH.to_owned(),
+        physics: PHYSICS_CONFIG_PATH.to_owned(),
+        opponent: None,
+        name: "Learner".to_owned(),
+        policy: None,
+        resume: false,
+        episodes: 2000,
+        seed: 0,
+        frame_skip: 4,
+        bins: 8,
+        features: Feature::DEFAULT.to_vec(),
+        learning_rate: 0.1,
+        discount: 0.99,
+        epsilon: 1.0,
+        min_epsilon: 0.05,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--resume" {
+            options.resume = true;
+            continue;
+        }
+
+        let value = args.next().ok_or(format!("{} expects a value", arg))?;
+        match arg.as_str() {
+            "--profiles" => options.profiles = value,
+            "--physics" => options.physics = value,
+            "--opponent" => options.opponent = Some(value),
+            "--name" => options.name = value,
+            "--policy" => options.policy = Some(value),
+            "--episodes" => options.episodes = parse(&arg, value)?,
+            "--seed" => options.seed = parse(&arg, value)?,
+            "--frame-skip" => options.frame_skip = parse(&arg, value)?,
+            "--bins" => options.bins = parse(&arg, value)?,
+            "--features" => options.features = parse_features(&value)?,
+            "--learning-rate" => options.learning_rate = parse(&arg, value)?,
+            "--discount" => options.discount = parse(&arg, value)?,
+            "--epsilon" => options.epsilon = parse(&arg, value)?,
+            "--min-epsilon" => options.min_epsilon = parse(&arg, value)?,
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+    Ok(options)
+}
+
+fn parse<T: std::str::FromStr>(arg: &str, value: String) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} got an invalid value {:?}", arg, value))
+}
+
+fn parse_features(value: &str) -> Result<Vec<Feature>, String> {
+    value.split(',').map(|name| name.trim().parse()).collect()
+}
+
+fn exit(error: impl std::fmt::Display) -> ! {
+    eprintln!("{}", error);
+    process::exit(1);
+}
@@ -0,0 +1,67 @@
+use std::ops::Add;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+pub struct SimTime(f64);
+
+impl SimTime {
+    pub const ZERO: Self = Self(0.0);
+
+    pub const fn from_secs(secs: f64) -> Self {
+        Self(secs)
+    }
+}
+
+impl SimTime {
+    pub fn since(self, earlier: Self) -> f64 {
+        self.0 - earlier.0
+    }
+}
+
+impl Add<f64> for SimTime {
+    type Output = Self;
+
+    fn add(self, secs: f64) -> Self {
+        Self(self.0 + secs)
+    }
+}
+
+pub struct Clock {
+    now: SimTime,
+    pub scale: f32,
+    pub paused: bool,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self {
+            now: SimTime::ZERO,
+            scale: 1.0,
+            paused: false,
+        }
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock {
+    pub fn now(&self) -> SimTime {
+        self.now
+    }
+
+    // Converts real frame time into the amount of simulation time that should elapse.
+    pub fn scaled(&self, frame_time: f32) -> f32 {
+        if self.paused {
+            0.0
+        } else {
+            frame_time * self.scale
+        }
+    }
+
+    pub fn tick(&mut self, dt: f32) {
+        self.now = self.now + dt as f64;
+    }
+}
@@ -0,0 +1,260 @@
+use macroquad::prelude::{vec2, Rect, Vec2};
+
+// The normal points from the other shape towards the first one, so moving the first shape by
+// `normal * depth` separates them.
+#[derive(Debug, Clone, Copy)]
+pub struct Contact {
+    pub normal: Vec2,
+    pub depth: f32,
+    pub point: Vec2,
+}
+
+impl Contact {
+    pub fn flipped(self) -> Self {
+        Self {
+            normal: -self.normal,
+            ..self
+        }
+    }
+}
+
+pub fn circle_rect(center: Vec2, radius: f32, rect: Rect) -> Option<Contact> {
+    let min = rect.point();
+    let max = min + rect.size();
+    let closest = center.clamp(min, max);
+    let offset = center - closest;
+    let distance_squared = offset.length_squared();
+
+    if distance_squared > 0.0 {
+        if distance_squared >= radius * radius {
+            return None;
+        }
+        let distance = distance_squared.sqrt();
+        return Some(Contact {
+            normal: offset / distance,
+            depth: radius - distance,
+            point: closest,
+        });
+    }
+
+    // The center is inside the rectangle, push it out through the nearest face.
+    let (distance, normal) = [
+        (center.x - min.x, -Vec2::X),
+        (max.x - center.x, Vec2::X),
+        (center.y - min.y, -Vec2::Y),
+        (max.y - center.y, Vec2::Y),
+    ]
+    .into_iter()
+    .min_by(|a, b| a.0.total_cmp(&b.0))?;
+
+    Some(Contact {
+        normal,
+        depth: distance + radius,
+        point: center + normal * distance,
+    })
+}
+
+pub fn circle_circle(center: Vec2, radius: f32, other: Vec2, other_radius: f32) -> Option<Contact> {
+    let offset = center - other;
+    let radii = radius + other_radius;
+    let distance_squared = offset.length_squared();
+    if distance_squared >= radii * radii {
+        return None;
+    }
+
+    let distance = distance_squared.sqrt();
+    let normal = if distance > 0.0 {
+        offset / distance
+    } else {
+        Vec2::Y
+    };
+
+    Some(Contact {
+        normal,
+        depth: radii - distance,
+        point: other + normal * other_radius,
+    })
+}
+
+pub fn rect_rect(rect: Rect, other: Rect) -> Option<Contact> {
+    let overlap = rect.intersect(other)?;
+    if overlap.w <= 0.0 || overlap.h <= 0.0 {
+        return None;
+    }
+
+    let offset = (rect.point() + rect.size() / 2.0) - (other.point() + other.size() / 2.0);
+    let (normal, depth) = if overlap.w < overlap.h {
+        (vec2(1.0f32.copysign(offset.x), 0.0), overlap.w)
+    } else {
+        (vec2(0.0, 1.0f32.copysign(offset.y)), overlap.h)
+    };
+
+    Some(Contact {
+        normal,
+        depth,
+        point: overlap.point() + overlap.size() / 2.0,
+    })
+}
+
+// Sweeps a point along `motion` against the box [min, max]. Returns the fraction of the motion
+// at which the box is entered and the normal of the face that was crossed.
+pub fn sweep_box(center: Vec2, motion: Vec2, min: Vec2, max: Vec2) -> Option<(f32, Vec2)> {
+    let mut entry = f32::NEG_INFINITY;
+    let mut exit = f32::INFINITY;
+    let mut normal = Vec2::ZERO;
+
+    for (axis, direction) in [(0, Vec2::X), (1, Vec2::Y)] {
+        if motion[axis] == 0.0 {
+            if center[axis] <= min[axis] || center[axis] >= max[axis] {
+                return None;
+            }
+            continue;
+        }
+
+        let near = (min[axis] - center[axis]) / motion[axis];
+        let far = (max[axis] - center[axis]) / motion[axis];
+        let (near, far) = if near < far { (near, far) } else { (far, near) };
+
+        if near > entry {
+            entry = near;
+            normal = -direction * motion[axis].signum();
+        }
+        exit = exit.min(far);
+    }
+
+    if entry > exit || !(0.0..1.0).contains(&entry) {
+        return None;
+    }
+
+    Some((entry, normal))
+}
+
+pub fn sweep_circle_circle(
+    center: Vec2,
+    radius: f32,
+    motion: Vec2,
+    other: Vec2,
+) -> Option<(f32, Vec2)> {
+    let offset = center - other;
+    let a = motion.length_squared();
+    let b = offset.dot(motion);
+    let c = offset.length_squared() - radius * radius;
+
+    // Not moving, moving away, or already overlapping.
+    if a == 0.0 || b >= 0.0 || c < 0.0 {
+        return None;
+    }
+
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let time = (-b - discriminant.sqrt()) / a;
+    if !(0.0..1.0).contains(&time) {
+        return None;
+    }
+
+    Some((time, (offset + motion * time).normalize()))
+}
+
+// Sweeps a circle against a rectangle, treating the rectangle as expanded by the radius with
+// rounded corners so hits near a corner get a diagonal normal.
+pub fn sweep_circle_rect(
+    center: Vec2,
+    radius: f32,
+    motion: Vec2,
+    rect: Rect,
+) -> Option<(f32, Vec2)> {
+    let min = rect.point();
+    let max = min + rect.size();
+    let expansion = Vec2::splat(radius);
+    let (time, normal) = sweep_box(center, motion, min - expansion, max + expansion)?;
+
+    let hit = center + motion * time;
+    let outside_x = hit.x < min.x || hit.x > max.x;
+    let outside_y = hit.y < min.y || hit.y > max.y;
+    if outside_x && outside_y {
+        let corner = vec2(
+            if hit.x < min.x { min.x } else { max.x },
+            if hit.y < min.y { min.y } else { max.y },
+        );
+        return sweep_circle_circle(center, radius, motion, corner);
+    }
+
+    Some((time, normal))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paddle() -> Rect {
+        Rect::new(100.0, 400.0, 15.0, 70.0)
+    }
+
+    fn assert_close(actual: Vec2, expected: Vec2) {
+        assert!(
+            (actual - expected).length() < 0.001,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn circle_touching_a_face_is_pushed_straight_out() {
+        let contact = circle_rect(vec2(120.0, 430.0), 8.0, paddle()).unwrap();
+        assert_close(contact.normal, vec2(1.0, 0.0));
+        assert!((contact.depth - 3.0).abs() < 0.001);
+        assert_close(contact.point, vec2(115.0, 430.0));
+    }
+
+    #[test]
+    fn circle_touching_a_corner_gets_a_diagonal_normal() {
+        let contact = circle_rect(vec2(120.0, 475.0), 8.0, paddle()).unwrap();
+        let diagonal = vec2(1.0, 1.0).normalize();
+        assert_close(contact.normal, diagonal);
+        assert!((contact.depth - (8.0 - 50.0f32.sqrt())).abs() < 0.001);
+        assert_close(contact.point, vec2(115.0, 470.0));
+
+        assert!(circle_rect(vec2(121.0, 476.0), 8.0, paddle()).is_none());
+    }
+
+    #[test]
+    fn circle_inside_a_rect_leaves_through_the_nearest_face() {
+        let contact = circle_rect(vec2(112.0, 430.0), 8.0, paddle()).unwrap();
+        assert_close(contact.normal, vec2(1.0, 0.0));
+        assert!((contact.depth - 11.0).abs() < 0.001);
+        assert_close(contact.point, vec2(115.0, 430.0));
+    }
+
+    #[test]
+    fn overlapping_circles_touch_on_the_other_surface() {
+        let contact = circle_circle(vec2(10.0, 0.0), 8.0, Vec2::ZERO, 5.0).unwrap();
+        assert_close(contact.normal, vec2(1.0, 0.0));
+        assert!((contact.depth - 3.0).abs() < 0.001);
+        assert_close(contact.point, vec2(5.0, 0.0));
+
+        let coincident = circle_circle(Vec2::ZERO, 8.0, Vec2::ZERO, 5.0).unwrap();
+        assert_close(coincident.normal, Vec2::Y);
+        assert!(circle_circle(vec2(13.0, 0.0), 8.0, Vec2::ZERO, 5.0).is_none());
+    }
+
+    #[test]
+    fn sweep_reports_a_hit_on_a_paddle_thinner_than_the_motion() {
+        let (time, normal) =
+            sweep_circle_rect(vec2(200.0, 435.0), 8.0, vec2(-200.0, 0.0), paddle()).unwrap();
+        assert!((time - (200.0 - 123.0) / 200.0).abs() < 0.001);
+        assert_close(normal, vec2(1.0, 0.0));
+    }
+
+    #[test]
+    fn sweep_into_a_corner_gets_a_diagonal_normal() {
+        let (time, normal) =
+            sweep_circle_rect(vec2(125.0, 480.0), 8.0, vec2(-10.0, -10.0), paddle()).unwrap();
+        let diagonal = vec2(1.0, 1.0).normalize();
+        assert!((time - (10.0 - 8.0 * diagonal.x) / 10.0).abs() < 0.001);
+        assert_close(normal, diagonal);
+    }
+}
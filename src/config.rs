@@ -0,0 +1,77 @@
+use std::{fmt, fs, io, path::Path, time::SystemTime};
+
+use serde::{de::DeserializeOwned, Serializer};
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(String, io::Error),
+    Parse(String, toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, error) => write!(f, "could not read {}: {}", path, error),
+            ConfigError::Parse(path, error) => write!(f, "could not parse {}: {}", path, error),
+            ConfigError::Invalid(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+pub fn load_toml<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, ConfigError> {
+    let path = path.as_ref().display().to_string();
+    let text = fs::read_to_string(&path).map_err(|error| ConfigError::Io(path.clone(), error))?;
+    toml::from_str(&text).map_err(|error| ConfigError::Parse(path, error))
+}
+
+// TOML only has 64 bit floats, so write the shortest decimal that reads back as the same f32
+// instead of its exact f64 value (0.1 rather than 0.10000000149011612).
+pub fn serialize_f32<S: Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(shortest_f64(*value))
+}
+
+pub fn serialize_f32_pair<S: Serializer>(
+    value: &(f32, f32),
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq([shortest_f64(value.0), shortest_f64(value.1)])
+}
+
+fn shortest_f64(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or(value as f64)
+}
+
+// Remembers when a file was last modified so it can be reloaded when it is saved again.
+pub struct FileWatcher {
+    path: String,
+    modified: Option<SystemTime>,
+}
+
+impl FileWatcher {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_owned(),
+            modified: modified_time(path),
+        }
+    }
+}
+
+impl FileWatcher {
+    pub fn changed(&mut self) -> bool {
+        let modified = modified_time(&self.path);
+        if modified.is_some() && modified != self.modified {
+            self.modified = modified;
+            return true;
+        }
+        false
+    }
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
@@ -12,5 +12,15 @@ pub static PLAYER_ACCELERATION: (f32, f32) = (0.0, 12000.0);
 pub static BOUNDS: Bounds = Bounds::new(0.0, 0.0, 1200.0, 1000.0);
 pub static BOUNDS_THICKNESS: f32 = 1000.0;
 
+pub static TICK_RATE: f32 = 240.0;
+pub static SERVE_DELAY: f64 = 1.0;
+pub static SERVE_SPEED: f32 = 1000.0;
+pub static ARENA_LIVES: u32 = 3;
+
+pub static PHYSICS_CONFIG_PATH: &str = "config/physics.toml";
+pub static AI_PROFILES_PATH: &str = "config/ai.toml";
+pub static RULES_PATH: &str = "config/rules.toml";
+pub static CONTROLS_PATH: &str = "config/controls.toml";
+
 pub const SARAH: Ai = Ai::new("Sarah", (0.1, 0.9), 0.95, 120);
 pub const RAPHAEL: Ai = Ai::new("Raphael", (-0.1, 1.1), 0.5, 450);
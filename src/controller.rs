@@ -0,0 +1,248 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use macroquad::prelude::{mouse_position, touches, vec2, Vec2};
+
+use crate::{
+    ai::Ai,
+    bounds::Bounds,
+    clock::SimTime,
+    controls::Binding,
+    events::{GameEvent, Side},
+    physics::GameObject,
+    rng::Rng,
+};
+
+pub struct ControlContext<'a> {
+    pub side: Side,
+    pub paddle: &'a GameObject,
+    pub opponent: &'a GameObject,
+    pub ball: &'a GameObject,
+    // Everything else the ball can bounce off.
+    pub walls: &'a [&'a GameObject],
+    pub events: &'a [GameEvent],
+    pub bounds: Bounds,
+    pub now: SimTime,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PaddleIntent {
+    // Leave the paddle where it is, without integrating its velocity.
+    Idle,
+    // Steer towards `target` (the paddle's top left corner) using the given fractions of the
+    // paddle's maximum velocity and acceleration.
+    MoveTo {
+        target: Vec2,
+        velocity_scale: f32,
+        acceleration_scale: f32,
+    },
+}
+
+impl PaddleIntent {
+    pub fn move_to(target: Vec2) -> Self {
+        PaddleIntent::MoveTo {
+            target,
+            velocity_scale: 1.0,
+            acceleration_scale: 1.0,
+        }
+    }
+}
+
+pub trait PaddleController {
+    fn name(&self) -> Option<&str> {
+        None
+    }
+
+    // Called by the render loop once per frame, outside of the simulation. Controllers reading
+    // devices sample them here so `control` stays deterministic across ticks. `to_field` takes a
+    // position on the screen to the field within `bounds`, as the paddle sees it.
+    fn poll(&mut self, _bounds: Bounds, _to_field: &dyn Fn(Vec2) -> Vec2) {}
+
+    // Called when the controller is assigned to a paddle, with the random stream of that paddle.
+    fn seed(&mut self, _rng: Rng) {}
+
+    fn control(&mut self, context: &ControlContext) -> PaddleIntent;
+
+    fn ai(&self) -> Option<&Ai<'static>> {
+        None
+    }
+
+    fn ai_mut(&mut self) -> Option<&mut Ai<'static>> {
+        None
+    }
+
+    // A few lines describing the controller's internal state, for debug overlays.
+    fn status(&self) -> Option<String> {
+        None
+    }
+}
+
+pub struct KeyboardController {
+    pub name: String,
+    pub up: Binding,
+    pub down: Binding,
+    up_down: (bool, bool),
+}
+
+impl KeyboardController {
+    pub fn new(name: &str, up: Binding, down: Binding) -> Self {
+        Self {
+            name: name.to_owned(),
+            up,
+            down,
+            up_down: (false, false),
+        }
+    }
+}
+
+impl PaddleController for KeyboardController {
+    fn name(&self) -> Option<&str> {
+        Some(&self.name)
+    }
+
+    fn poll(&mut self, _bounds: Bounds, _to_field: &dyn Fn(Vec2) -> Vec2) {
+        self.up_down = (self.up.is_down(), self.down.is_down());
+    }
+
+    fn control(&mut self, context: &ControlContext) -> PaddleIntent {
+        let position = context.paddle.position;
+        let target = match self.up_down {
+            (true, false) => vec2(position.x, -f32::INFINITY),
+            (false, true) => vec2(position.x, f32::INFINITY),
+            _ => position,
+        };
+        PaddleIntent::MoveTo {
+            target,
+            velocity_scale: 0.5,
+            acceleration_scale: 0.6,
+        }
+    }
+}
+
+// With a `zone`, only pointers over that half of the field move the paddle, so two players can
+// share a touch screen or a mouse.
+#[derive(Default)]
+pub struct MouseController {
+    name: Option<String>,
+    zone: Option<Side>,
+    position: Option<Vec2>,
+}
+
+impl MouseController {
+    pub fn new(name: &str, zone: Option<Side>) -> Self {
+        Self {
+            name: Some(name.to_owned()),
+            zone,
+            position: None,
+        }
+    }
+}
+
+impl PaddleController for MouseController {
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn poll(&mut self, bounds: Bounds, to_field: &dyn Fn(Vec2) -> Vec2) {
+        let position = to_field(mouse_position().into());
+        if in_zone(self.zone, position, bounds) {
+            self.position = Some(position);
+        }
+    }
+
+    fn control(&mut self, context: &ControlContext) -> PaddleIntent {
+        follow_pointer(self.position, context)
+    }
+}
+
+// Follows the most recent touch in its zone, and stays on the last touched position once the
+// finger lifts.
+#[derive(Default)]
+pub struct TouchController {
+    name: Option<String>,
+    zone: Option<Side>,
+    position: Option<Vec2>,
+}
+
+impl TouchController {
+    pub fn new(name: &str, zone: Option<Side>) -> Self {
+        Self {
+            name: Some(name.to_owned()),
+            zone,
+            position: None,
+        }
+    }
+}
+
+impl PaddleController for TouchController {
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn poll(&mut self, bounds: Bounds, to_field: &dyn Fn(Vec2) -> Vec2) {
+        let touch = touches()
+            .iter()
+            .rev()
+            .map(|touch| to_field(touch.position))
+            .find(|&position| in_zone(self.zone, position, bounds));
+        if let Some(position) = touch {
+            self.position = Some(position);
+        }
+    }
+
+    fn control(&mut self, context: &ControlContext) -> PaddleIntent {
+        follow_pointer(self.position, context)
+    }
+}
+
+// Drives a paddle from intents sent by another thread, e.g. one reading from a socket. The last
+// received intent is repeated until a new one arrives.
+pub struct RemoteController {
+    name: String,
+    receiver: Receiver<PaddleIntent>,
+    intent: PaddleIntent,
+}
+
+impl RemoteController {
+    pub fn new(name: &str) -> (Self, Sender<PaddleIntent>) {
+        let (sender, receiver) = channel();
+        let controller = Self {
+            name: name.to_owned(),
+            receiver,
+            intent: PaddleIntent::Idle,
+        };
+        (controller, sender)
+    }
+}
+
+impl PaddleController for RemoteController {
+    fn name(&self) -> Option<&str> {
+        Some(&self.name)
+    }
+
+    fn poll(&mut self, _bounds: Bounds, _to_field: &dyn Fn(Vec2) -> Vec2) {
+        if let Some(intent) = self.receiver.try_iter().last() {
+            self.intent = intent;
+        }
+    }
+
+    fn control(&mut self, _context: &ControlContext) -> PaddleIntent {
+        self.intent
+    }
+}
+
+fn in_zone(zone: Option<Side>, position: Vec2, bounds: Bounds) -> bool {
+    match zone {
+        Some(Side::Left) => position.x < bounds.center().x,
+        Some(Side::Right) => position.x >= bounds.center().x,
+        None => true,
+    }
+}
+
+fn follow_pointer(position: Option<Vec2>, context: &ControlContext) -> PaddleIntent {
+    match position {
+        Some(position) => {
+            PaddleIntent::move_to(position - context.paddle.collider.rect.size() / 2.0)
+        }
+        None => PaddleIntent::Idle,
+    }
+}
@@ -0,0 +1,479 @@
+use std::fs;
+
+use macroquad::prelude::{
+    is_key_down, is_key_pressed, is_mouse_button_down, is_mouse_button_pressed, KeyCode,
+    MouseButton,
+};
+use serde::{de, Deserialize, Deserializer};
+use toml_edit::{table, value, DocumentMut};
+
+use crate::{
+    config::{load_toml, ConfigError},
+    events::Side,
+    gamepad::GamepadSettings,
+};
+
+// Every key but `Unknown` can be bound, written in the controls file by its `KeyCode` name.
+const BINDABLE_KEYS: [KeyCode; 120] = [
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::Space,
+    KeyCode::Enter,
+    KeyCode::Escape,
+    KeyCode::Tab,
+    KeyCode::Backspace,
+    KeyCode::Insert,
+    KeyCode::Delete,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::CapsLock,
+    KeyCode::ScrollLock,
+    KeyCode::NumLock,
+    KeyCode::PrintScreen,
+    KeyCode::Pause,
+    KeyCode::Menu,
+    KeyCode::Apostrophe,
+    KeyCode::Comma,
+    KeyCode::Minus,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Semicolon,
+    KeyCode::Equal,
+    KeyCode::LeftBracket,
+    KeyCode::Backslash,
+    KeyCode::RightBracket,
+    KeyCode::GraveAccent,
+    KeyCode::World1,
+    KeyCode::World2,
+    KeyCode::Kp0,
+    KeyCode::Kp1,
+    KeyCode::Kp2,
+    KeyCode::Kp3,
+    KeyCode::Kp4,
+    KeyCode::Kp5,
+    KeyCode::Kp6,
+    KeyCode::Kp7,
+    KeyCode::Kp8,
+    KeyCode::Kp9,
+    KeyCode::KpDecimal,
+    KeyCode::KpDivide,
+    KeyCode::KpMultiply,
+    KeyCode::KpSubtract,
+    KeyCode::KpAdd,
+    KeyCode::KpEnter,
+    KeyCode::KpEqual,
+    KeyCode::LeftShift,
+    KeyCode::LeftControl,
+    KeyCode::LeftAlt,
+    KeyCode::LeftSuper,
+    KeyCode::RightShift,
+    KeyCode::RightControl,
+    KeyCode::RightAlt,
+    KeyCode::RightSuper,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::F13,
+    KeyCode::F14,
+    KeyCode::F15,
+    KeyCode::F16,
+    KeyCode::F17,
+    KeyCode::F18,
+    KeyCode::F19,
+    KeyCode::F20,
+    KeyCode::F21,
+    KeyCode::F22,
+    KeyCode::F23,
+    KeyCode::F24,
+    KeyCode::F25,
+];
+
+const MOUSE_BUTTONS: [(MouseButton, &str); 3] = [
+    (MouseButton::Left, "MouseLeft"),
+    (MouseButton::Middle, "MouseMiddle"),
+    (MouseButton::Right, "MouseRight"),
+];
+
+// A key or mouse button triggering an action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+impl Binding {
+    pub fn parse(name: &str) -> Option<Self> {
+        let key = BINDABLE_KEYS
+            .into_iter()
+            .find(|&key| format!("{:?}", key).eq_ignore_ascii_case(name))
+            .map(Binding::Key);
+        let button = MOUSE_BUTTONS
+            .into_iter()
+            .find(|(_, button_name)| button_name.eq_ignore_ascii_case(name))
+            .map(|(button, _)| Binding::Mouse(button));
+        key.or(button)
+    }
+
+    pub fn name(self) -> String {
+        match self {
+            Binding::Key(key) => format!("{:?}", key),
+            Binding::Mouse(button) => MOUSE_BUTTONS
+                .into_iter()
+                .find(|&(other, _)| other == button)
+                .map_or_else(|| format!("{:?}", button), |(_, name)| name.to_owned()),
+        }
+    }
+
+    pub fn is_down(self) -> bool {
+        match self {
+            Binding::Key(key) => is_key_down(key),
+            Binding::Mouse(button) => is_mouse_button_down(button),
+        }
+    }
+
+    pub fn is_pressed(self) -> bool {
+        match self {
+            Binding::Key(key) => is_key_pressed(key),
+            Binding::Mouse(button) => is_mouse_button_pressed(button),
+        }
+    }
+
+    // Whichever bindable key or mouse button was pressed this frame, for rebinding.
+    pub fn pressed() -> Option<Self> {
+        let keys = BINDABLE_KEYS.into_iter().map(Binding::Key);
+        let buttons = MOUSE_BUTTONS
+            .into_iter()
+            .map(|(button, _)| Binding::Mouse(button));
+        keys.chain(buttons).find(|binding| binding.is_pressed())
+    }
+}
+
+fn deserialize_binding<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Binding, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    Binding::parse(&name)
+        .ok_or_else(|| de::Error::custom(format!("unknown key or mouse button {:?}", name)))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    MoveUp(Side),
+    MoveDown(Side),
+    // Switches a side between the AI and the keyboard.
+    ToggleKeyboard(Side),
+    // Switches the side under the pointer between the AI and the keyboard.
+    ToggleKeyboardAtPointer,
+    // Switches the paddle under the pointer between the AI and the pointer.
+    TogglePointer,
+    Pause,
+    Serve,
+}
+
+// In the order they are listed on the rebinding screen.
+pub const ACTIONS: [Action; 10] = [
+    Action::MoveUp(Side::Left),
+    Action::MoveDown(Side::Left),
+    Action::ToggleKeyboard(Side::Left),
+    Action::MoveUp(Side::Right),
+    Action::MoveDown(Side::Right),
+    Action::ToggleKeyboard(Side::Right),
+    Action::ToggleKeyboardAtPointer,
+    Action::TogglePointer,
+    Action::Pause,
+    Action::Serve,
+];
+
+impl Action {
+    pub fn label(self) -> String {
+        match self {
+            Action::MoveUp(side) => format!("{:?} up", side),
+            Action::MoveDown(side) => format!("{:?} down", side),
+            Action::ToggleKeyboard(side) => format!("{:?} keyboard control", side),
+            Action::ToggleKeyboardAtPointer => "Keyboard control at pointer".to_owned(),
+            Action::TogglePointer => "Pointer control".to_owned(),
+            Action::Pause => "Pause".to_owned(),
+            Action::Serve => "Serve".to_owned(),
+        }
+    }
+
+    // The table and key holding the binding in the controls file.
+    fn path(self) -> (&'static str, &'static str) {
+        let table = |side| match side {
+            Side::Left => "left",
+            Side::Right => "right",
+        };
+        match self {
+            Action::MoveUp(side) => (table(side), "up"),
+            Action::MoveDown(side) => (table(side), "down"),
+            Action::ToggleKeyboard(side) => (table(side), "keyboard"),
+            Action::ToggleKeyboardAtPointer => ("actions", "keyboard"),
+            Action::TogglePointer => ("actions", "pointer"),
+            Action::Pause => ("actions", "pause"),
+            Action::Serve => ("actions", "serve"),
+        }
+    }
+}
+
+// How one human player plays: the name shown on the HUD, the keys moving their paddle and the
+// one handing it to the keyboard.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlayerControls {
+    pub name: String,
+    #[serde(deserialize_with = "deserialize_binding")]
+    pub up: Binding,
+    #[serde(deserialize_with = "deserialize_binding")]
+    pub down: Binding,
+    #[serde(deserialize_with = "deserialize_binding")]
+    pub keyboard: Binding,
+}
+
+// Bindings of the actions that aren't tied to a player.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ActionBindings {
+    #[serde(deserialize_with = "deserialize_binding")]
+    pub keyboard: Binding,
+    #[serde(deserialize_with = "deserialize_binding")]
+    pub pointer: Binding,
+    #[serde(deserialize_with = "deserialize_binding")]
+    pub pause: Binding,
+    #[serde(deserialize_with = "deserialize_binding")]
+    pub serve: Binding,
+}
+
+impl Default for ActionBindings {
+    fn default() -> Self {
+        Self {
+            keyboard: Binding::Mouse(MouseButton::Middle),
+            pointer: Binding::Mouse(MouseButton::Left),
+            pause: Binding::Key(KeyCode::Escape),
+            serve: Binding::Key(KeyCode::Space),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Controls {
+    #[serde(default = "Controls::left")]
+    pub left: PlayerControls,
+    #[serde(default = "Controls::right")]
+    pub right: PlayerControls,
+    #[serde(default)]
+    pub actions: ActionBindings,
+    #[serde(default)]
+    pub gamepad: GamepadSettings,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Self {
+            left: Self::left(),
+            right: Self::right(),
+            actions: ActionBindings::default(),
+            gamepad: GamepadSettings::default(),
+        }
+    }
+}
+
+impl Controls {
+    pub fn load(path: &str) -> Result<Self, ConfigError> {
+        let controls: Self = load_toml(path)?;
+        if let Some((action, other)) = controls.conflicts().first() {
+            return Err(ConfigError::Invalid(format!(
+                "{} and {} are both bound to {}",
+                action.label(),
+                other.label(),
+                controls.binding(*action).name()
+            )));
+        }
+        controls.gamepad.validate()?;
+        Ok(controls)
+    }
+
+    // Writes the bindings and player names into the controls file, keeping the rest of it.
+    pub fn save(&self, path: &str) -> Result<(), ConfigError> {
+        let text = fs::read_to_string(path).unwrap_or_default();
+        let mut document: DocumentMut = text.parse().map_err(|error| {
+            ConfigError::Invalid(format!("could not parse {}: {}", path, error))
+        })?;
+
+        for name in ["left", "right", "actions"] {
+            document.entry(name).or_insert(table());
+        }
+        for (table, player) in [("left", &self.left), ("right", &self.right)] {
+            document[table]["name"] = value(player.name.as_str());
+        }
+        for action in ACTIONS {
+            let (table, key) = action.path();
+            document[table][key] = value(self.binding(action).name());
+        }
+        fs::write(path, document.to_string())
+            .map_err(|error| ConfigError::Io(path.to_owned(), error))
+    }
+
+    fn left() -> PlayerControls {
+        PlayerControls {
+            name: "Player 1".to_owned(),
+            up: Binding::Key(KeyCode::W),
+            down: Binding::Key(KeyCode::S),
+            keyboard: Binding::Key(KeyCode::Key1),
+        }
+    }
+
+    fn right() -> PlayerControls {
+        PlayerControls {
+            name: "Player 2".to_owned(),
+            up: Binding::Key(KeyCode::Up),
+            down: Binding::Key(KeyCode::Down),
+            keyboard: Binding::Key(KeyCode::Key2),
+        }
+    }
+}
+
+impl Controls {
+    pub fn player(&self, side: Side) -> &PlayerControls {
+        match side {
+            Side::Left => &self.left,
+            Side::Right => &self.right,
+        }
+    }
+
+    fn player_mut(&mut self, side: Side) -> &mut PlayerControls {
+        match side {
+            Side::Left => &mut self.left,
+            Side::Right => &mut self.right,
+        }
+    }
+
+    pub fn binding(&self, action: Action) -> Binding {
+        match action {
+            Action::MoveUp(side) => self.player(side).up,
+            Action::MoveDown(side) => self.player(side).down,
+            Action::ToggleKeyboard(side) => self.player(side).keyboard,
+            Action::ToggleKeyboardAtPointer => self.actions.keyboard,
+            Action::TogglePointer => self.actions.pointer,
+            Action::Pause => self.actions.pause,
+            Action::Serve => self.actions.serve,
+        }
+    }
+
+    pub fn binding_mut(&mut self, action: Action) -> &mut Binding {
+        match action {
+            Action::MoveUp(side) => &mut self.player_mut(side).up,
+            Action::MoveDown(side) => &mut self.player_mut(side).down,
+            Action::ToggleKeyboard(side) => &mut self.player_mut(side).keyboard,
+            Action::ToggleKeyboardAtPointer => &mut self.actions.keyboard,
+            Action::TogglePointer => &mut self.actions.pointer,
+            Action::Pause => &mut self.actions.pause,
+            Action::Serve => &mut self.actions.serve,
+        }
+    }
+
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.binding(action).is_pressed()
+    }
+
+    // Pairs of actions sharing a binding, in the order of `ACTIONS`.
+    pub fn conflicts(&self) -> Vec<(Action, Action)> {
+        let mut conflicts = Vec::new();
+        for (index, &action) in ACTIONS.iter().enumerate() {
+            for &other in &ACTIONS[index + 1..] {
+                if self.binding(action) == self.binding(other) {
+                    conflicts.push((action, other));
+                }
+            }
+        }
+        conflicts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("{}-{}.toml", name, std::process::id()));
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn saved_bindings_load_back() {
+        let path = temp_path("controls-round-trip");
+        let mut controls = Controls::default();
+        controls.left.name = "Ada".to_owned();
+        *controls.binding_mut(Action::MoveUp(Side::Left)) = Binding::Key(KeyCode::F9);
+        *controls.binding_mut(Action::Pause) = Binding::Key(KeyCode::Escape);
+        controls.save(&path).unwrap();
+
+        let loaded = Controls::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.left.name, "Ada");
+        for action in ACTIONS {
+            assert_eq!(loaded.binding(action), controls.binding(action));
+        }
+    }
+
+    #[test]
+    fn saved_conflict_fails_to_load() {
+        let path = temp_path("controls-conflict");
+        let mut controls = Controls::default();
+        *controls.binding_mut(Action::Pause) = Binding::Key(KeyCode::F12);
+        *controls.binding_mut(Action::Serve) = Binding::Key(KeyCode::F12);
+        controls.save(&path).unwrap();
+
+        let error = Controls::load(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            error,
+            ConfigError::Invalid(message) if message == "Pause and Serve are both bound to F12"
+        ));
+    }
+}
@@ -0,0 +1,123 @@
+use std::sync::mpsc::Sender;
+
+use crate::{
+    ai::AiProfile,
+    controller::{PaddleIntent, RemoteController},
+    events::{GameEvent, Side},
+    game::GameState,
+    learning::{observe, Action, Feature},
+    physics::PhysicsConfig,
+    rules::Rules,
+};
+
+#[derive(Debug, Clone)]
+pub struct EnvConfig {
+    pub side: Side,
+    pub opponent: AiProfile,
+    pub features: Vec<Feature>,
+    // Physics ticks simulated per `step`, repeating the chosen action.
+    pub frame_skip: u32,
+    // The episode ends once either player reaches this score.
+    pub points: u32,
+    // Ends episodes where nobody scores, counted in calls to `step`.
+    pub max_steps: u32,
+    // Reward for each hit of the agent's paddle, on top of +1 per point won and -1 per point lost.
+    pub hit_reward: f32,
+    pub physics: PhysicsConfig,
+}
+
+impl EnvConfig {
+    pub fn new(opponent: AiProfile) -> Self {
+        Self {
+            side: Side::Left,
+            opponent,
+            features: Feature::DEFAULT.to_vec(),
+            frame_skip: 4,
+            points: 1,
+            max_steps: 10_000,
+            hit_reward: 0.1,
+            physics: PhysicsConfig::default(),
+        }
+    }
+}
+
+// A gym-style environment where an agent plays one paddle against an AI profile, on the same
+// simulation as the game.
+pub struct PongEnv {
+    pub config: EnvConfig,
+    pub state: GameState,
+    agent: Sender<PaddleIntent>,
+    steps: u32,
+}
+
+impl PongEnv {
+    pub fn new(config: EnvConfig, seed: u64) -> Self {
+        let (state, agent) = Self::new_game(&config, seed);
+        Self {
+            config,
+            state,
+            agent,
+            steps: 0,
+        }
+    }
+
+    fn new_game(config: &EnvConfig, seed: u64) -> (GameState, Sender<PaddleIntent>) {
+        let (agent, sender) = RemoteController::new("Agent");
+        let opponent = config.opponent.controller();
+        let mut state = match config.side {
+            Side::Left => GameState::new(Box::new(agent), opponent, seed),
+            Side::Right => GameState::new(opponent, Box::new(agent), seed),
+        };
+        state.rules = Rules::first_to(config.points);
+        state.apply_physics(&config.physics);
+        (state, sender)
+    }
+}
+
+impl PongEnv {
+    pub fn reset(&mut self, seed: u64) -> Vec<f32> {
+        let (state, agent) = Self::new_game(&self.config, seed);
+        self.state = state;
+        self.agent = agent;
+        self.steps = 0;
+        self.observation()
+    }
+
+    pub fn observation(&self) -> Vec<f32> {
+        let side = self.config.side;
+        observe(
+            &self.config.features,
+            side,
+            &self.state.player(side).object,
+            &self.state.player(side.opponent()).object,
+            &self.state.ball,
+            self.state.player(side).bounds,
+        )
+    }
+
+    pub fn step(&mut self, action: Action) -> (Vec<f32>, f32, bool) {
+        let side = self.config.side;
+        let intent = action.intent(self.state.player(side).object.position);
+        // The receiving end lives in the game state, which is only dropped along with this sender.
+        self.agent.send(intent).expect("agent controller is alive");
+        self.state.poll_controllers();
+
+        let mut reward = 0.0;
+        let dt = self.state.timestep.tick;
+        for _ in 0..self.config.frame_skip {
+            for event in self.state.step(dt) {
+                match event {
+                    GameEvent::Goal { scorer } if scorer == side => reward += 1.0,
+                    GameEvent::Goal { .. } => reward -= 1.0,
+                    event if event.is_hit_by(side) => reward += self.config.hit_reward,
+                    _ => {}
+                }
+            }
+        }
+        self.steps += 1;
+
+        let done = self.state.winner.is_some() || self.steps >= self.config.max_steps;
+
+        (self.observation(), reward, done)
+    }
+}
@@ -0,0 +1,50 @@
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    pub fn opponent(self) -> Self {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wall {
+    Top,
+    Bottom,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum GameEvent {
+    // `offset` goes from -1.0 (top edge of the paddle) to 1.0 (bottom edge).
+    PaddleHit {
+        player: Side,
+        offset: f32,
+        speed: f32,
+    },
+    WallBounce {
+        wall: Wall,
+    },
+    Goal {
+        scorer: Side,
+    },
+    GameWon {
+        winner: Side,
+    },
+    MatchWon {
+        winner: Side,
+    },
+}
+
+impl GameEvent {
+    pub fn is_hit_by(&self, side: Side) -> bool {
+        matches!(*self, GameEvent::PaddleHit { player, .. } if player == side)
+    }
+}
@@ -0,0 +1,318 @@
+use std::thread;
+
+use crate::{
+    ai::{AiProfile, PredictionMode},
+    rng::{Rng, EVOLUTION_STREAM},
+    tournament::{play_match, MatchSettings},
+};
+
+// Name and range of every evolved AI knob, in genome order. The prediction gene picks the
+// simulated prediction above one half and the analytic one below.
+pub const GENES: [(&str, f32, f32); 12] = [
+    ("hit_range_low", -0.3, 0.5),
+    ("hit_range_high", 0.5, 1.3),
+    ("accuracy", 0.0, 1.0),
+    ("reaction_time", 0.0, 800.0),
+    ("max_speed", 0.2, 1.0),
+    ("anticipation", 0.0, 1.0),
+    ("aggression", 0.0, 1.0),
+    ("prediction", 0.0, 1.0),
+    ("speed_reaction", 0.0, 1.5),
+    ("fatigue", 0.0, 0.3),
+    ("overshoot", 0.0, 1.0),
+    ("hesitation", 0.0, 300.0),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Genome(pub [f32; GENES.len()]);
+
+impl Genome {
+    pub fn random(rng: &mut Rng) -> Self {
+        let mut genes = [0.0; GENES.len()];
+        for (gene, (_, min, max)) in genes.iter_mut().zip(GENES) {
+            *gene = rng.gen_range(min, max);
+        }
+        Self(genes)
+    }
+
+    pub fn from_profile(profile: &AiProfile) -> Self {
+        let genes = [
+            profile.hit_range.0,
+            profile.hit_range.1,
+            profile.accuracy,
+            profile.reaction_time as f32,
+            profile.max_speed,
+            profile.anticipation,
+            profile.aggression,
+            match profile.prediction {
+                PredictionMode::Analytic => 0.0,
+                PredictionMode::Simulated => 1.0,
+            },
+            profile.speed_reaction,
+            profile.fatigue,
+            profile.overshoot,
+            profile.hesitation as f32,
+        ];
+        let mut genome = Self(genes);
+        genome.clamp();
+        genome
+    }
+}
+
+impl Genome {
+    // Genes are rounded to three decimals to keep the written profile readable.
+    pub fn to_profile(&self, name: &str) -> AiProfile {
+        let [low, high, accuracy, reaction_time, max_speed, anticipation, aggression, prediction, speed_reaction, fatigue, overshoot, hesitation] =
+            self.0.map(|gene| (gene * 1000.0).round() / 1000.0);
+        AiProfile {
+            name: name.to_owned(),
+            hit_range: (low, high),
+            accuracy,
+            reaction_time: reaction_time.round() as u16,
+            max_speed,
+            anticipation,
+            aggression,
+            prediction: if prediction >= 0.5 {
+                PredictionMode::Simulated
+            } else {
+                PredictionMode::Analytic
+            },
+            speed_reaction,
+            fatigue,
+            overshoot,
+            hesitation: hesitation.round() as u16,
+            policy: None,
+            script: None,
+            learned: None,
+        }
+    }
+
+    // Takes each gene from either parent.
+    pub fn crossover(&self, other: &Genome, rng: &mut Rng) -> Genome {
+        let mut child = *self;
+        for (gene, other_gene) in child.0.iter_mut().zip(other.0) {
+            if rng.gen_bool() {
+                *gene = other_gene;
+            }
+        }
+        child
+    }
+
+    // Nudges each gene with probability `rate`, by up to `strength` times the range of the gene.
+    pub fn mutate(&mut self, rate: f32, strength: f32, rng: &mut Rng) {
+        for (gene, (_, min, max)) in self.0.iter_mut().zip(GENES) {
+            if rng.next_f32() < rate {
+                *gene += rng.gen_range(-strength, strength) * (max - min);
+            }
+        }
+        self.clamp();
+    }
+
+    fn clamp(&mut self) {
+        for (gene, (_, min, max)) in self.0.iter_mut().zip(GENES) {
+            *gene = gene.clamp(min, max);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Evaluation {
+    // Wins plus half the draws, over the matches played.
+    pub win_rate: f32,
+    // Points scored over all points played.
+    pub point_share: f32,
+    pub fitness: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct EvolutionSettings {
+    pub population: usize,
+    pub elites: usize,
+    pub tournament_size: usize,
+    pub mutation_rate: f32,
+    pub mutation_strength: f32,
+    // Matches per reference profile and side.
+    pub games: u32,
+    pub target_win_rate: f32,
+    pub matches: MatchSettings,
+}
+
+impl Default for EvolutionSettings {
+    fn default() -> Self {
+        Self {
+            population: 24,
+            elites: 2,
+            tournament_size: 3,
+            mutation_rate: 0.3,
+            mutation_strength: 0.15,
+            games: 2,
+            target_win_rate: 1.0,
+            matches: MatchSettings {
+                points: 5,
+                max_time: 60.0,
+                ..MatchSettings::default()
+            },
+        }
+    }
+}
+
+// Plays a genome against every reference profile on both sides. Fitness is highest when the
+// win rate hits the target, with the point share breaking ties between equal win rates.
+pub fn evaluate(
+    genome: &Genome,
+    references: &[AiProfile],
+    settings: &EvolutionSettings,
+    seed: u64,
+) -> Evaluation {
+    let candidate = genome.to_profile("Candidate");
+    let mut outcomes = 0.0;
+    let mut matches = 0;
+    let mut points = (0, 0);
+
+    for reference in references {
+        for game in 0..settings.games {
+            let match_seed = seed.wrapping_add(game as u64);
+            let as_left = play_match(&candidate, reference, match_seed, settings.matches);
+            let as_right = play_match(reference, &candidate, match_seed, settings.matches);
+
+            outcomes += as_left.left_outcome() + (1.0 - as_right.left_outcome());
+            matches += 2;
+            points.0 += as_left.score.0 + as_right.score.1;
+            points.1 += as_left.score.1 + as_right.score.0;
+        }
+    }
+
+    let win_rate = (outcomes / matches.max(1) as f64) as f32;
+    let point_share = if points.0 + points.1 == 0 {
+        0.5
+    } else {
+        points.0 as f32 / (points.0 + points.1) as f32
+    };
+    let target = settings.target_win_rate;
+    let fitness = -(win_rate - target).abs() - 0.1 * (point_share - target).abs();
+
+    Evaluation {
+        win_rate,
+        point_share,
+        fitness,
+    }
+}
+
+pub struct Evolution {
+    pub settings: EvolutionSettings,
+    pub references: Vec<AiProfile>,
+    pub population: Vec<(Genome, Evaluation)>,
+    pub generation: u32,
+    seed: u64,
+    rng: Rng,
+}
+
+impl Evolution {
+    // `seeds` are extra genomes, such as existing profiles, to start the population from.
+    pub fn new(
+        settings: EvolutionSettings,
+        references: Vec<AiProfile>,
+        seeds: &[Genome],
+        seed: u64,
+    ) -> Self {
+        let mut rng = Rng::new(seed, EVOLUTION_STREAM);
+        let mut genomes: Vec<Genome> = seeds.iter().copied().take(settings.population).collect();
+        while genomes.len() < settings.population {
+            genomes.push(Genome::random(&mut rng));
+        }
+
+        let mut evolution = Self {
+            settings,
+            references,
+            population: Vec::new(),
+            generation: 0,
+            seed,
+            rng,
+        };
+        evolution.population = evolution.evaluate_all(genomes);
+        evolution
+    }
+}
+
+impl Evolution {
+    pub fn best(&self) -> &(Genome, Evaluation) {
+        &self.population[0]
+    }
+
+    pub fn mean_fitness(&self) -> f32 {
+        self.population
+            .iter()
+            .map(|(_, evaluation)| evaluation.fitness)
+            .sum::<f32>()
+            / self.population.len() as f32
+    }
+
+    // Breeds the next generation, keeping the elites unchanged but re-evaluated on the new seeds.
+    pub fn next_generation(&mut self) {
+        self.generation += 1;
+
+        let mut genomes: Vec<Genome> = self
+            .population
+            .iter()
+            .take(self.settings.elites)
+            .map(|(genome, _)| *genome)
+            .collect();
+        while genomes.len() < self.settings.population {
+            let mother = self.select();
+            let father = self.select();
+            let mut child = mother.crossover(&father, &mut self.rng);
+            child.mutate(
+                self.settings.mutation_rate,
+                self.settings.mutation_strength,
+                &mut self.rng,
+            );
+            genomes.push(child);
+        }
+
+        self.population = self.evaluate_all(genomes);
+    }
+
+    fn select(&mut self) -> Genome {
+        let mut best = self.rng.next_u32() as usize % self.population.len();
+        for _ in 1..self.settings.tournament_size {
+            let contender = self.rng.next_u32() as usize % self.population.len();
+            // The population is sorted by fitness, so a lower index is fitter.
+            best = best.min(contender);
+        }
+        self.population[best].0
+    }
+
+    // Every genome of a generation plays the same seeds, spread over the available cores.
+    fn evaluate_all(&self, genomes: Vec<Genome>) -> Vec<(Genome, Evaluation)> {
+        let seed = self
+            .seed
+            .wrapping_add(self.generation as u64 * self.settings.games as u64);
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+        let chunk_size = genomes.len().div_ceil(threads).max(1);
+
+        let mut population: Vec<(Genome, Evaluation)> = thread::scope(|scope| {
+            let handles: Vec<_> = genomes
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|genome| {
+                                let evaluation =
+                                    evaluate(genome, &self.references, &self.settings, seed);
+                                (*genome, evaluation)
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("evaluation thread panicked"))
+                .collect()
+        });
+
+        population.sort_by(|a, b| b.1.fitness.total_cmp(&a.1.fitness));
+        population
+    }
+}
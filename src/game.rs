@@ -0,0 +1,474 @@
+use macroquad::prelude::{vec2, Vec2};
+
+use crate::{
+    clock::{Clock, SimTime},
+    constants::*,
+    controller::{ControlContext, PaddleController},
+    events::{GameEvent, Side, Wall},
+    physics::{ColliderType, Collision, GameObject, Material, PhysicsConfig},
+    player::Player,
+    rng::{Rng, LEFT_AI_STREAM, RIGHT_AI_STREAM, SERVE_STREAM},
+    rules::Rules,
+    timestep::FixedTimestep,
+};
+
+pub struct GameState {
+    pub ball: GameObject,
+    pub player_left: Player,
+    pub player_right: Player,
+    pub controller_left: Box<dyn PaddleController>,
+    pub controller_right: Box<dyn PaddleController>,
+
+    pub top_bound: GameObject,
+    pub bottom_bound: GameObject,
+    pub left_bound: GameObject,
+    pub right_bound: GameObject,
+
+    pub timestep: FixedTimestep,
+    pub clock: Clock,
+    pub serve_time: Option<SimTime>,
+
+    pub rules: Rules,
+    pub server: Side,
+    pub serves_left: u32,
+    // Who served first in the current game, the other player opens the next one.
+    pub opening_server: Side,
+    pub winner: Option<Side>,
+
+    pub seed: u64,
+    pub serve_rng: Rng,
+
+    pub events: Vec<GameEvent>,
+}
+
+impl GameState {
+    pub fn new(
+        controller_left: Box<dyn PaddleController>,
+        controller_right: Box<dyn PaddleController>,
+        seed: u64,
+    ) -> Self {
+        let top_bound = GameObject::from_pos(
+            BOUNDS.x,
+            BOUNDS.y - BOUNDS_THICKNESS,
+            ColliderType::Rectangle(BOUNDS.w, BOUNDS_THICKNESS),
+        );
+        let bottom_bound = GameObject::from_pos(
+            BOUNDS.x,
+            BOUNDS.h,
+            ColliderType::Rectangle(BOUNDS.w, BOUNDS_THICKNESS),
+        );
+
+        let mut left_bound = GameObject::from_pos(
+            BOUNDS.x - BOUNDS_THICKNESS,
+            BOUNDS.y,
+            ColliderType::Rectangle(BOUNDS_THICKNESS, BOUNDS.h),
+        );
+        let mut right_bound = GameObject::from_pos(
+            BOUNDS.w,
+            BOUNDS.y,
+            ColliderType::Rectangle(BOUNDS_THICKNESS, BOUNDS.h),
+        );
+        left_bound.is_sensor = true;
+        right_bound.is_sensor = true;
+
+        let paddle_left = GameObject::from_pos(
+            BOUNDS.x + PLAYER_PADDING,
+            BOUNDS.center().y - PLAYER_HEIGHT / 2.0,
+            ColliderType::Rectangle(PLAYER_WIDTH, PLAYER_HEIGHT),
+        );
+        let paddle_right = GameObject::from_pos(
+            BOUNDS.w - PLAYER_PADDING - PLAYER_WIDTH,
+            BOUNDS.center().y - PLAYER_HEIGHT / 2.0,
+            ColliderType::Rectangle(PLAYER_WIDTH, PLAYER_HEIGHT),
+        );
+
+        let player_left = Player::new(
+            "Player 1",
+            paddle_left,
+            BOUNDS,
+            PLAYER_VELOCITY.into(),
+            PLAYER_ACCELERATION.into(),
+        );
+        let player_right = Player::new(
+            "Player 2",
+            paddle_right,
+            BOUNDS,
+            PLAYER_VELOCITY.into(),
+            PLAYER_ACCELERATION.into(),
+        );
+
+        let mut ball = GameObject::from_pos(
+            BOUNDS.center().x - BALL_RADIUS,
+            BOUNDS.center().y - BALL_RADIUS,
+            ColliderType::Circle(BALL_RADIUS),
+        );
+        ball.material = Material::BALL;
+
+        let mut serve_rng = Rng::new(seed, SERVE_STREAM);
+        let server = if serve_rng.gen_bool() {
+            Side::Left
+        } else {
+            Side::Right
+        };
+        let rules = Rules::default();
+
+        let mut state = Self {
+            ball,
+            player_left,
+            player_right,
+            controller_left,
+            controller_right,
+
+            top_bound,
+            bottom_bound,
+            left_bound,
+            right_bound,
+
+            timestep: FixedTimestep::new(TICK_RATE),
+            clock: Clock::new(),
+            serve_time: Some(SimTime::ZERO),
+
+            serves_left: rules.serves,
+            rules,
+            server,
+            opening_server: server,
+            winner: None,
+
+            seed,
+            serve_rng,
+
+            events: Vec::new(),
+        };
+        for side in [Side::Left, Side::Right] {
+            state.seat_controller(side);
+        }
+        state
+    }
+}
+
+impl GameState {
+    pub fn advance(&mut self, frame_time: f32) {
+        self.events.clear();
+
+        let sim_time = self.clock.scaled(frame_time);
+        for _ in 0..self.timestep.accumulate(sim_time) {
+            let events = self.step(self.timestep.tick);
+            self.events.extend(events);
+        }
+    }
+
+    pub fn alpha(&self) -> f32 {
+        self.timestep.alpha()
+    }
+
+    pub fn reset_ball(&mut self) {
+        if self.winner.is_none() {
+            self.serve_time = Some(self.clock.now());
+        }
+    }
+
+    // Starts a new match, the other player serving first.
+    pub fn rematch(&mut self) {
+        for player in [&mut self.player_left, &mut self.player_right] {
+            player.score = 0;
+            player.games = 0;
+        }
+        self.winner = None;
+        self.start_game();
+    }
+
+    pub fn set_rules(&mut self, rules: Rules) {
+        self.rules = rules;
+        self.rematch();
+    }
+
+    pub fn scores(&self) -> (u32, u32) {
+        (self.player_left.score, self.player_right.score)
+    }
+
+    fn start_game(&mut self) {
+        self.opening_server = self.opening_server.opponent();
+        self.server = self.opening_server;
+        self.serves_left = self.rules.serves;
+        self.serve_time = Some(self.clock.now());
+    }
+
+    pub fn apply_physics(&mut self, config: &PhysicsConfig) {
+        self.ball.material = config.ball;
+        self.player_left.object.material = config.paddle;
+        self.player_right.object.material = config.paddle;
+        self.top_bound.material = config.wall;
+        self.bottom_bound.material = config.wall;
+    }
+
+    pub fn set_controller(&mut self, side: Side, controller: Box<dyn PaddleController>) {
+        match side {
+            Side::Left => self.controller_left = controller,
+            Side::Right => self.controller_right = controller,
+        }
+        self.seat_controller(side);
+    }
+
+    // Gives a newly assigned controller the random stream of its paddle, and its name.
+    fn seat_controller(&mut self, side: Side) {
+        let (player, controller, stream, default_name) = match side {
+            Side::Left => (
+                &mut self.player_left,
+                &mut self.controller_left,
+                LEFT_AI_STREAM,
+                "Player 1",
+            ),
+            Side::Right => (
+                &mut self.player_right,
+                &mut self.controller_right,
+                RIGHT_AI_STREAM,
+                "Player 2",
+            ),
+        };
+        controller.seed(Rng::new(self.seed, stream));
+        player.name = controller.name().unwrap_or(default_name).to_owned();
+    }
+
+    pub fn controller(&self, side: Side) -> &dyn PaddleController {
+        match side {
+            Side::Left => self.controller_left.as_ref(),
+            Side::Right => self.controller_right.as_ref(),
+        }
+    }
+
+    pub fn controller_mut(&mut self, side: Side) -> &mut dyn PaddleController {
+        match side {
+            Side::Left => self.controller_left.as_mut(),
+            Side::Right => self.controller_right.as_mut(),
+        }
+    }
+
+    // Samples input devices once per rendered frame, before the simulation is advanced.
+    pub fn poll_controllers(&mut self) {
+        let (left, right) = (self.player_left.bounds, self.player_right.bounds);
+        self.controller_left
+            .poll(left, &|position| left.convert_to_local(position));
+        self.controller_right
+            .poll(right, &|position| right.convert_to_local(position));
+    }
+
+    pub fn player(&self, side: Side) -> &Player {
+        match side {
+            Side::Left => &self.player_left,
+            Side::Right => &self.player_right,
+        }
+    }
+
+    pub fn player_mut(&mut self, side: Side) -> &mut Player {
+        match side {
+            Side::Left => &mut self.player_left,
+            Side::Right => &mut self.player_right,
+        }
+    }
+
+    pub fn step(&mut self, dt: f32) -> Vec<GameEvent> {
+        self.clock.tick(dt);
+        let now = self.clock.now();
+
+        self.ball.store_previous_position();
+        self.player_left.object.store_previous_position();
+        self.player_right.object.store_previous_position();
+
+        if let Some(serve_time) = self.serve_time {
+            self.ball.position = BOUNDS.center() - Vec2::from(BALL_SIZE) / 2.0;
+            self.ball.velocity = vec2(0.0, 0.0);
+            self.ball.angular_velocity = 0.0;
+            self.ball.store_previous_position();
+
+            if now > serve_time + SERVE_DELAY {
+                let direction = match self.server {
+                    Side::Left => 1.0,
+                    Side::Right => -1.0,
+                };
+                self.ball.velocity = vec2(
+                    direction * SERVE_SPEED,
+                    self.serve_rng.gen_range(-400.0, 400.0),
+                );
+                self.serve_time = None;
+            }
+        }
+
+        //___PHYSICS___//
+
+        let collisions = self.ball.handle_bounces(
+            &[
+                &self.player_left.object,
+                &self.player_right.object,
+                &self.top_bound,
+                &self.bottom_bound,
+                &self.left_bound,
+                &self.right_bound,
+            ],
+            dt,
+        );
+        let mut events: Vec<GameEvent> = collisions
+            .iter()
+            .map(|collision| self.collision_event(collision))
+            .collect();
+
+        //___SCORING___//
+
+        let goals: Vec<Side> = events
+            .iter()
+            .filter_map(|event| match *event {
+                GameEvent::Goal { scorer } => Some(scorer),
+                _ => None,
+            })
+            .collect();
+        for scorer in goals {
+            if self.winner.is_none() {
+                self.score_point(scorer, now, &mut events);
+            }
+        }
+
+        //___PLAYERS___//
+
+        self.control_player(Side::Left, &events, now, dt);
+        self.control_player(Side::Right, &events, now, dt);
+
+        events
+    }
+
+    fn score_point(&mut self, scorer: Side, now: SimTime, events: &mut Vec<GameEvent>) {
+        self.player_mut(scorer).scored();
+        let scores = self.scores();
+
+        let Some(winner) = self.rules.game_winner(scores) else {
+            (self.server, self.serves_left) =
+                self.rules
+                    .next_server(self.server, self.serves_left, scorer, scores);
+            self.serve_time = Some(now);
+            return;
+        };
+        events.push(GameEvent::GameWon { winner });
+
+        let player = self.player_mut(winner);
+        player.games = player.games.saturating_add(1);
+        if player.games < self.rules.games_to_win() {
+            self.player_left.score = 0;
+            self.player_right.score = 0;
+            self.start_game();
+            return;
+        }
+
+        // The final score stays up and the ball waits in the middle until a rematch.
+        events.push(GameEvent::MatchWon { winner });
+        self.winner = Some(winner);
+        self.serve_time = None;
+        self.ball.position = BOUNDS.center() - Vec2::from(BALL_SIZE) / 2.0;
+        self.ball.velocity = vec2(0.0, 0.0);
+        self.ball.angular_velocity = 0.0;
+        self.ball.collider.update_pos(self.ball.position);
+    }
+
+    // Indices follow the order of the objects passed to `handle_bounces` in `step`.
+    fn collision_event(&self, collision: &Collision) -> GameEvent {
+        match collision.index {
+            0 | 1 => {
+                let player = if collision.index == 0 {
+                    Side::Left
+                } else {
+                    Side::Right
+                };
+                let paddle = &self.player(player).object.collider;
+                GameEvent::PaddleHit {
+                    player,
+                    offset: (collision.contact.point.y - paddle.center().y) / (paddle.rect.h / 2.0),
+                    speed: collision.velocity.length(),
+                }
+            }
+            2 => GameEvent::WallBounce { wall: Wall::Top },
+            3 => GameEvent::WallBounce { wall: Wall::Bottom },
+            4 => GameEvent::Goal {
+                scorer: Side::Right,
+            },
+            _ => GameEvent::Goal { scorer: Side::Left },
+        }
+    }
+
+    fn control_player(&mut self, side: Side, events: &[GameEvent], now: SimTime, dt: f32) {
+        let (player, opponent, controller) = match side {
+            Side::Left => (
+                &mut self.player_left,
+                &self.player_right,
+                &mut self.controller_left,
+            ),
+            Side::Right => (
+                &mut self.player_right,
+                &self.player_left,
+                &mut self.controller_right,
+            ),
+        };
+
+        let intent = controller.control(&ControlContext {
+            side,
+            paddle: &player.object,
+            opponent: &opponent.object,
+            ball: &self.ball,
+            walls: &[&self.top_bound, &self.bottom_bound],
+            events,
+            bounds: player.bounds,
+            now,
+        });
+        player.apply_intent(intent, dt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::RemoteController;
+
+    // Paddles whose senders are dropped right away, so they never move.
+    fn idle_game(seed: u64) -> GameState {
+        GameState::new(
+            Box::new(RemoteController::new("Left").0),
+            Box::new(RemoteController::new("Right").0),
+            seed,
+        )
+    }
+
+    #[test]
+    fn same_seed_plays_the_same_rally() {
+        let mut first = idle_game(7);
+        let mut second = idle_game(7);
+        let dt = first.timestep.tick;
+        for _ in 0..(3.0 / dt) as usize {
+            first.step(dt);
+            second.step(dt);
+        }
+        assert_ne!(first.ball.velocity, vec2(0.0, 0.0));
+        assert_eq!(first.ball.position, second.ball.position);
+        assert_eq!(first.ball.velocity, second.ball.velocity);
+    }
+
+    #[test]
+    fn goal_scores_a_point_and_serves_again() {
+        let mut state = idle_game(3);
+        let dt = state.timestep.tick;
+        let scorer = (0..(60.0 / dt) as usize)
+            .find_map(|_| {
+                state.step(dt).into_iter().find_map(|event| match event {
+                    GameEvent::Goal { scorer } => Some(scorer),
+                    _ => None,
+                })
+            })
+            .expect("idle paddles let a goal through");
+
+        assert_eq!(state.player(scorer).score, 1);
+        assert_eq!(state.player(scorer.opponent()).score, 0);
+        assert!(state.serve_time.is_some());
+
+        state.step(dt);
+        assert_eq!(
+            state.ball.position,
+            BOUNDS.center() - Vec2::from(BALL_SIZE) / 2.0
+        );
+        assert_eq!(state.ball.velocity, vec2(0.0, 0.0));
+    }
+}
@@ -0,0 +1,415 @@
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+use macroquad::prelude::{vec2, Vec2};
+use serde::Deserialize;
+
+use crate::{
+    bounds::Bounds,
+    config::ConfigError,
+    controller::{ControlContext, PaddleController, PaddleIntent},
+};
+
+pub type GamepadId = usize;
+
+// One backend serves every paddle played with a gamepad, and the menu assigning them.
+pub type SharedGamepads = Rc<RefCell<dyn GamepadBackend>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GamepadEvent {
+    Connected(GamepadId),
+    Disconnected(GamepadId),
+}
+
+pub trait GamepadBackend {
+    // Processes pending input, returning the gamepads plugged in or out since the last call.
+    fn poll(&mut self) -> Vec<GamepadEvent>;
+
+    fn connected(&self) -> Vec<GamepadId>;
+
+    fn name(&self, id: GamepadId) -> Option<String>;
+
+    // Position of the left stick along `axis`, from -1.0 (left or up) to 1.0 (right or down), or
+    // None if the gamepad isn't connected.
+    fn stick(&self, id: GamepadId, axis: StickAxis) -> Option<f32>;
+}
+
+// The stick axis moving a paddle along its length: vertical for paddles guarding the sides,
+// horizontal for the top and bottom paddles of a four-player arena.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StickAxis {
+    Horizontal,
+    Vertical,
+}
+
+// The real backend when built with the `gamepad` feature, otherwise one without any gamepads.
+#[cfg(feature = "gamepad")]
+pub fn default_backend() -> SharedGamepads {
+    match GilrsGamepads::new() {
+        Ok(gamepads) => Rc::new(RefCell::new(gamepads)),
+        Err(error) => {
+            eprintln!("Gamepads are unavailable, {}", error);
+            Rc::new(RefCell::new(FakeGamepads::default()))
+        }
+    }
+}
+
+#[cfg(not(feature = "gamepad"))]
+pub fn default_backend() -> SharedGamepads {
+    Rc::new(RefCell::new(FakeGamepads::default()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StickMode {
+    // The stick position maps to a paddle position, centered when the stick is released.
+    Absolute,
+    // The stick sets the paddle speed, and the paddle stops when it is released.
+    Velocity,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct GamepadSettings {
+    pub mode: StickMode,
+    // Stick deflection ignored around the center, from 0.0 to 1.0.
+    pub dead_zone: f32,
+    // Multiplies the stick deflection once past the dead zone.
+    pub sensitivity: f32,
+}
+
+impl Default for GamepadSettings {
+    fn default() -> Self {
+        Self {
+            mode: StickMode::Velocity,
+            dead_zone: 0.15,
+            sensitivity: 1.0,
+        }
+    }
+}
+
+impl GamepadSettings {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if !(0.0..1.0).contains(&self.dead_zone) {
+            return Err(ConfigError::Invalid(
+                "gamepad: dead_zone must be at least 0 and less than 1".to_owned(),
+            ));
+        }
+        if !(self.sensitivity > 0.0 && self.sensitivity.is_finite()) {
+            return Err(ConfigError::Invalid(
+                "gamepad: sensitivity must be positive".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl GamepadSettings {
+    // Rescales the stick so it starts from 0.0 at the edge of the dead zone.
+    pub fn apply(&self, value: f32) -> f32 {
+        let magnitude = (value.abs() - self.dead_zone).max(0.0) / (1.0 - self.dead_zone);
+        (value.signum() * magnitude * self.sensitivity).clamp(-1.0, 1.0)
+    }
+}
+
+pub struct GamepadController {
+    name: String,
+    id: GamepadId,
+    gamepads: SharedGamepads,
+    settings: GamepadSettings,
+    axis: StickAxis,
+    stick: Option<f32>,
+}
+
+impl GamepadController {
+    pub fn new(
+        name: &str,
+        id: GamepadId,
+        gamepads: SharedGamepads,
+        settings: GamepadSettings,
+        axis: StickAxis,
+    ) -> Self {
+        Self {
+            name: name.to_owned(),
+            id,
+            gamepads,
+            settings,
+            axis,
+            stick: None,
+        }
+    }
+}
+
+impl PaddleController for GamepadController {
+    fn name(&self) -> Option<&str> {
+        Some(&self.name)
+    }
+
+    fn poll(&mut self, _bounds: Bounds, _to_field: &dyn Fn(Vec2) -> Vec2) {
+        let stick = self.gamepads.borrow().stick(self.id, self.axis);
+        self.stick = stick.map(|value| self.settings.apply(value));
+    }
+
+    fn control(&mut self, context: &ControlContext) -> PaddleIntent {
+        let Some(stick) = self.stick else {
+            return PaddleIntent::Idle;
+        };
+        let position = context.paddle.position;
+
+        match self.settings.mode {
+            StickMode::Absolute => {
+                let height = context.paddle.collider.rect.h;
+                let center = context.bounds.center().y;
+                let reach = (context.bounds.height() - height) / 2.0;
+                PaddleIntent::move_to(vec2(position.x, center + stick * reach - height / 2.0))
+            }
+            StickMode::Velocity if stick == 0.0 => PaddleIntent::move_to(position),
+            StickMode::Velocity => PaddleIntent::MoveTo {
+                target: vec2(position.x, stick.signum() * f32::INFINITY),
+                velocity_scale: stick.abs(),
+                acceleration_scale: 1.0,
+            },
+        }
+    }
+
+    fn status(&self) -> Option<String> {
+        Some(match self.stick {
+            Some(stick) => format!("Gamepad {}\nStick {:.2}", self.id, stick),
+            None => format!("Gamepad {}\nDisconnected", self.id),
+        })
+    }
+}
+
+// A backend driven by code rather than devices, for headless runs and builds without gamepads.
+#[derive(Default)]
+pub struct FakeGamepads {
+    gamepads: BTreeMap<GamepadId, (String, Vec2)>,
+    events: Vec<GamepadEvent>,
+}
+
+impl FakeGamepads {
+    pub fn connect(&mut self, id: GamepadId, name: &str) {
+        self.gamepads.insert(id, (name.to_owned(), Vec2::ZERO));
+        self.events.push(GamepadEvent::Connected(id));
+    }
+
+    pub fn disconnect(&mut self, id: GamepadId) {
+        if self.gamepads.remove(&id).is_some() {
+            self.events.push(GamepadEvent::Disconnected(id));
+        }
+    }
+
+    pub fn set_stick(&mut self, id: GamepadId, axis: StickAxis, value: f32) {
+        if let Some((_, stick)) = self.gamepads.get_mut(&id) {
+            let value = value.clamp(-1.0, 1.0);
+            match axis {
+                StickAxis::Horizontal => stick.x = value,
+                StickAxis::Vertical => stick.y = value,
+            }
+        }
+    }
+}
+
+impl GamepadBackend for FakeGamepads {
+    fn poll(&mut self) -> Vec<GamepadEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn connected(&self) -> Vec<GamepadId> {
+        self.gamepads.keys().copied().collect()
+    }
+
+    fn name(&self, id: GamepadId) -> Option<String> {
+        self.gamepads.get(&id).map(|(name, _)| name.clone())
+    }
+
+    fn stick(&self, id: GamepadId, axis: StickAxis) -> Option<f32> {
+        self.gamepads.get(&id).map(|&(_, stick)| match axis {
+            StickAxis::Horizontal => stick.x,
+            StickAxis::Vertical => stick.y,
+        })
+    }
+}
+
+#[cfg(feature = "gamepad")]
+pub struct GilrsGamepads {
+    gilrs: gilrs::Gilrs,
+}
+
+#[cfg(feature = "gamepad")]
+impl GilrsGamepads {
+    pub fn new() -> Result<Self, gilrs::Error> {
+        Ok(Self {
+            gilrs: gilrs::Gilrs::new()?,
+        })
+    }
+
+    fn gamepad(&self, id: GamepadId) -> Option<gilrs::Gamepad<'_>> {
+        self.gilrs
+            .gamepads()
+            .find(|(gamepad_id, _)| usize::from(*gamepad_id) == id)
+            .map(|(_, gamepad)| gamepad)
+    }
+}
+
+#[cfg(feature = "gamepad")]
+impl GamepadBackend for GilrsGamepads {
+    fn poll(&mut self) -> Vec<GamepadEvent> {
+        let mut events = Vec::new();
+        while let Some(gilrs::Event { id, event, .. }) = self.gilrs.next_event() {
+            match event {
+                gilrs::EventType::Connected => events.push(GamepadEvent::Connected(id.into())),
+                gilrs::EventType::Disconnected => {
+                    events.push(GamepadEvent::Disconnected(id.into()))
+                }
+                _ => {}
+            }
+        }
+        events
+    }
+
+    fn connected(&self) -> Vec<GamepadId> {
+        self.gilrs
+            .gamepads()
+            .filter(|(_, gamepad)| gamepad.is_connected())
+            .map(|(id, _)| id.into())
+            .collect()
+    }
+
+    fn name(&self, id: GamepadId) -> Option<String> {
+        self.gamepad(id).map(|gamepad| gamepad.name().to_owned())
+    }
+
+    // Gilrs points the Y axis up, the game points it down.
+    fn stick(&self, id: GamepadId, axis: StickAxis) -> Option<f32> {
+        let gamepad = self.gamepad(id).filter(|gamepad| gamepad.is_connected())?;
+        Some(match axis {
+            StickAxis::Horizontal => gamepad.value(gilrs::Axis::LeftStickX),
+            StickAxis::Vertical => -gamepad.value(gilrs::Axis::LeftStickY),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        arena::{ArenaState, Edge, EDGES},
+        controller::RemoteController,
+        events::Side,
+        game::GameState,
+    };
+
+    // A game with idle paddles where the left one is then handed to gamepad 0.
+    fn game_with_gamepad(gamepads: &Rc<RefCell<FakeGamepads>>) -> GameState {
+        let mut state = GameState::new(
+            Box::new(RemoteController::new("Left").0),
+            Box::new(RemoteController::new("Right").0),
+            1,
+        );
+        let shared: SharedGamepads = gamepads.clone();
+        let settings = GamepadSettings::default();
+        let controller = GamepadController::new("Pad", 0, shared, settings, StickAxis::Vertical);
+        state.set_controller(Side::Left, Box::new(controller));
+        state
+    }
+
+    fn left_intent(state: &mut GameState) -> PaddleIntent {
+        let now = state.clock.now();
+        state.controller_left.control(&ControlContext {
+            side: Side::Left,
+            paddle: &state.player_left.object,
+            opponent: &state.player_right.object,
+            ball: &state.ball,
+            walls: &[&state.top_bound, &state.bottom_bound],
+            events: &[],
+            bounds: state.player_left.bounds,
+            now,
+        })
+    }
+
+    #[test]
+    fn stick_steers_the_assigned_paddle() {
+        let gamepads = Rc::new(RefCell::new(FakeGamepads::default()));
+        gamepads.borrow_mut().connect(0, "Fake pad");
+        assert_eq!(gamepads.borrow_mut().poll(), [GamepadEvent::Connected(0)]);
+        let mut state = game_with_gamepad(&gamepads);
+
+        gamepads.borrow_mut().set_stick(0, StickAxis::Vertical, 1.0);
+        state.poll_controllers();
+        let intent = left_intent(&mut state);
+        assert!(matches!(
+            intent,
+            PaddleIntent::MoveTo { target, velocity_scale, .. }
+                if target.y == f32::INFINITY && velocity_scale == 1.0
+        ));
+
+        gamepads
+            .borrow_mut()
+            .set_stick(0, StickAxis::Vertical, -0.1);
+        state.poll_controllers();
+        let intent = left_intent(&mut state);
+        let position = state.player_left.object.position;
+        assert!(matches!(
+            intent,
+            PaddleIntent::MoveTo { target, .. } if target == position
+        ));
+    }
+
+    #[test]
+    fn disconnected_gamepad_leaves_the_paddle_idle() {
+        let gamepads = Rc::new(RefCell::new(FakeGamepads::default()));
+        gamepads.borrow_mut().connect(0, "Fake pad");
+        let mut state = game_with_gamepad(&gamepads);
+        gamepads.borrow_mut().set_stick(0, StickAxis::Vertical, 1.0);
+        state.poll_controllers();
+
+        gamepads.borrow_mut().disconnect(0);
+        assert_eq!(
+            gamepads.borrow_mut().poll(),
+            [GamepadEvent::Connected(0), GamepadEvent::Disconnected(0)]
+        );
+        state.poll_controllers();
+        assert!(matches!(left_intent(&mut state), PaddleIntent::Idle));
+        assert_eq!(
+            state.controller(Side::Left).status().as_deref(),
+            Some("Gamepad 0\nDisconnected")
+        );
+    }
+
+    #[test]
+    fn horizontal_stick_steers_a_top_paddle_sideways() {
+        let gamepads = Rc::new(RefCell::new(FakeGamepads::default()));
+        gamepads.borrow_mut().connect(0, "Fake pad");
+        let controllers = EDGES.map(|edge| {
+            Box::new(RemoteController::new(edge.default_name()).0) as Box<dyn PaddleController>
+        });
+        let mut arena = ArenaState::new(controllers, 3, 1);
+        let shared: SharedGamepads = gamepads.clone();
+        let settings = GamepadSettings::default();
+        let controller = GamepadController::new("Pad", 0, shared, settings, StickAxis::Horizontal);
+        arena.set_controller(Edge::Top, Box::new(controller));
+
+        let start = arena.seat(Edge::Top).player.object.position;
+        let mut hold = |axis, value| {
+            let mut gamepads = gamepads.borrow_mut();
+            gamepads.set_stick(0, StickAxis::Horizontal, 0.0);
+            gamepads.set_stick(0, StickAxis::Vertical, 0.0);
+            gamepads.set_stick(0, axis, value);
+            drop(gamepads);
+            arena.poll_controllers();
+            let dt = arena.timestep.tick;
+            for _ in 0..(0.5 / dt) as usize {
+                arena.step(dt);
+            }
+            arena.seat(Edge::Top).player.object.position
+        };
+
+        // Pushing the stick up or down leaves a flat paddle where it is.
+        assert_eq!(hold(StickAxis::Vertical, -1.0), start);
+        let right = hold(StickAxis::Horizontal, 1.0);
+        assert!(right.x > start.x);
+        assert_eq!(right.y, start.y);
+        let left = hold(StickAxis::Horizontal, -1.0);
+        assert!(left.x < right.x);
+    }
+}
@@ -0,0 +1,295 @@
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+
+use macroquad::prelude::{vec2, Vec2};
+use serde::{
+    de::{value::StrDeserializer, IntoDeserializer},
+    Deserialize, Serialize,
+};
+
+use crate::{
+    bounds::Bounds,
+    config::{load_toml, ConfigError},
+    controller::{ControlContext, PaddleController, PaddleIntent},
+    events::Side,
+    physics::GameObject,
+};
+
+const MAX_OBSERVED_VELOCITY: f32 = 2000.0;
+const MAX_OBSERVED_SPIN: f32 = 20.0;
+
+// One entry of an observation vector. Every feature is scaled to roughly -1.0..1.0, and
+// horizontal features are mirrored for the right paddle so a policy plays the same on both sides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    BallX,
+    BallY,
+    BallVelocityX,
+    BallVelocityY,
+    BallSpin,
+    PaddleY,
+    PaddleVelocityY,
+    OpponentY,
+    // Ball height relative to the paddle center, in paddle heights.
+    RelativeBallY,
+}
+
+impl Feature {
+    pub const DEFAULT: [Feature; 4] = [
+        Feature::BallX,
+        Feature::BallVelocityX,
+        Feature::BallVelocityY,
+        Feature::RelativeBallY,
+    ];
+}
+
+impl FromStr for Feature {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, String> {
+        let deserializer: StrDeserializer<serde::de::value::Error> = name.into_deserializer();
+        Feature::deserialize(deserializer).map_err(|_| format!("unknown feature {:?}", name))
+    }
+}
+
+pub fn observe(
+    features: &[Feature],
+    side: Side,
+    paddle: &GameObject,
+    opponent: &GameObject,
+    ball: &GameObject,
+    bounds: Bounds,
+) -> Vec<f32> {
+    let mirror = match side {
+        Side::Left => 1.0,
+        Side::Right => -1.0,
+    };
+    let center = bounds.center();
+    let (half_width, half_height) = (bounds.width() / 2.0, bounds.height() / 2.0);
+    let ball_center = ball.collider.center();
+
+    features
+        .iter()
+        .map(|feature| {
+            let value = match feature {
+                Feature::BallX => mirror * (ball_center.x - center.x) / half_width,
+                Feature::BallY => (ball_center.y - center.y) / half_height,
+                Feature::BallVelocityX => mirror * ball.velocity.x / MAX_OBSERVED_VELOCITY,
+                Feature::BallVelocityY => ball.velocity.y / MAX_OBSERVED_VELOCITY,
+                Feature::BallSpin => mirror * ball.angular_velocity / MAX_OBSERVED_SPIN,
+                Feature::PaddleY => (paddle.collider.center().y - center.y) / half_height,
+                Feature::PaddleVelocityY => paddle.velocity.y / MAX_OBSERVED_VELOCITY,
+                Feature::OpponentY => (opponent.collider.center().y - center.y) / half_height,
+                Feature::RelativeBallY => {
+                    (ball_center.y - paddle.collider.center().y) / paddle.collider.rect.h
+                }
+            };
+            value.clamp(-1.0, 1.0)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Up,
+    Stay,
+    Down,
+}
+
+impl Action {
+    pub const ALL: [Action; 3] = [Action::Up, Action::Stay, Action::Down];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    // The intent is fixed when the action is chosen and held until the next decision, so
+    // `Stay` brakes towards where the paddle was at that moment.
+    pub fn intent(self, paddle_position: Vec2) -> PaddleIntent {
+        let target = match self {
+            Action::Up => vec2(paddle_position.x, -f32::INFINITY),
+            Action::Stay => paddle_position,
+            Action::Down => vec2(paddle_position.x, f32::INFINITY),
+        };
+        PaddleIntent::move_to(target)
+    }
+}
+
+// A tabular action-value function over discretized observations.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "StoredPolicy", try_from = "StoredPolicy")]
+pub struct QPolicy {
+    pub features: Vec<Feature>,
+    pub bins: u8,
+    pub frame_skip: u32,
+    pub values: HashMap<u64, [f32; 3]>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredPolicy {
+    features: Vec<Feature>,
+    bins: u8,
+    frame_skip: u32,
+    table: Vec<(u64, f32, f32, f32)>,
+}
+
+impl From<QPolicy> for StoredPolicy {
+    fn from(policy: QPolicy) -> Self {
+        let mut table: Vec<_> = policy
+            .values
+            .into_iter()
+            .map(|(state, [up, stay, down])| (state, up, stay, down))
+            .collect();
+        table.sort_by_key(|entry| entry.0);
+        Self {
+            features: policy.features,
+            bins: policy.bins,
+            frame_skip: policy.frame_skip,
+            table,
+        }
+    }
+}
+
+impl TryFrom<StoredPolicy> for QPolicy {
+    type Error = String;
+
+    fn try_from(stored: StoredPolicy) -> Result<Self, String> {
+        let policy = QPolicy::new(&stored.features, stored.bins, stored.frame_skip)?;
+        let states = policy.state_count();
+        let mut values = HashMap::new();
+        for (state, up, stay, down) in stored.table {
+            if state >= states {
+                return Err(format!("state {} is out of range", state));
+            }
+            values.insert(state, [up, stay, down]);
+        }
+        Ok(Self { values, ..policy })
+    }
+}
+
+impl QPolicy {
+    pub fn new(features: &[Feature], bins: u8, frame_skip: u32) -> Result<Self, String> {
+        if features.is_empty() {
+            return Err("a policy needs at least one feature".to_owned());
+        }
+        if bins < 2 {
+            return Err("bins must be at least 2".to_owned());
+        }
+        if frame_skip == 0 {
+            return Err("frame_skip must be at least 1".to_owned());
+        }
+        // Keeps state indices representable as TOML integers.
+        if (bins as f64).powi(features.len() as i32) > i64::MAX as f64 {
+            return Err("too many states, use fewer features or bins".to_owned());
+        }
+
+        Ok(Self {
+            features: features.to_vec(),
+            bins,
+            frame_skip,
+            values: HashMap::new(),
+        })
+    }
+
+    pub fn load(path: &str) -> Result<Self, ConfigError> {
+        load_toml(path)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = toml::to_string(self).map_err(|error| error.to_string())?;
+        std::fs::write(path, text).map_err(|error| format!("could not write {}: {}", path, error))
+    }
+}
+
+impl QPolicy {
+    pub fn state_count(&self) -> u64 {
+        (self.bins as u64).pow(self.features.len() as u32)
+    }
+
+    pub fn state(&self, observation: &[f32]) -> u64 {
+        let bins = self.bins as u64;
+        observation.iter().fold(0, |state, value| {
+            let bin = ((value + 1.0) / 2.0 * self.bins as f32).floor() as i64;
+            state * bins + bin.clamp(0, bins as i64 - 1) as u64
+        })
+    }
+
+    pub fn values(&self, state: u64) -> [f32; 3] {
+        self.values.get(&state).copied().unwrap_or_default()
+    }
+
+    // Ties go to `Stay`, so unvisited states leave the paddle alone.
+    pub fn best_action(&self, state: u64) -> Action {
+        let values = self.values(state);
+        let mut best = Action::Stay;
+        for action in Action::ALL {
+            if values[action.index()] > values[best.index()] {
+                best = action;
+            }
+        }
+        best
+    }
+
+    // One step of Q-learning towards `reward` plus the discounted value of the next state.
+    pub fn update(
+        &mut self,
+        state: u64,
+        action: Action,
+        reward: f32,
+        next_state: Option<u64>,
+        learning_rate: f32,
+        discount: f32,
+    ) {
+        let next_value = next_state.map_or(0.0, |next_state| {
+            self.values(next_state)
+                .into_iter()
+                .fold(f32::NEG_INFINITY, f32::max)
+        });
+        let values = self.values.entry(state).or_default();
+        let value = &mut values[action.index()];
+        *value += learning_rate * (reward + discount * next_value - *value);
+    }
+}
+
+// Plays a trained policy, deciding every `frame_skip` ticks like during training.
+pub struct PolicyController {
+    name: String,
+    policy: Arc<QPolicy>,
+    ticks_left: u32,
+    intent: PaddleIntent,
+}
+
+impl PolicyController {
+    pub fn new(name: &str, policy: Arc<QPolicy>) -> Self {
+        Self {
+            name: name.to_owned(),
+            policy,
+            ticks_left: 0,
+            intent: PaddleIntent::Idle,
+        }
+    }
+}
+
+impl PaddleController for PolicyController {
+    fn name(&self) -> Option<&str> {
+        Some(&self.name)
+    }
+
+    fn control(&mut self, context: &ControlContext) -> PaddleIntent {
+        if self.ticks_left == 0 {
+            self.ticks_left = self.policy.frame_skip;
+            let observation = observe(
+                &self.policy.features,
+                context.side,
+                context.paddle,
+                context.opponent,
+                context.ball,
+                context.bounds,
+            );
+            let action = self.policy.best_action(self.policy.state(&observation));
+            self.intent = action.intent(context.paddle.position);
+        }
+        self.ticks_left -= 1;
+        self.intent
+    }
+}
@@ -0,0 +1,24 @@
+pub mod adaptive;
+pub mod ai;
+pub mod arena;
+pub mod bounds;
+pub mod clock;
+pub mod collision;
+pub mod config;
+pub mod constants;
+pub mod controller;
+pub mod controls;
+pub mod env;
+pub mod events;
+pub mod evolution;
+pub mod game;
+pub mod gamepad;
+pub mod learning;
+pub mod physics;
+pub mod player;
+pub mod rng;
+pub mod rules;
+pub mod script;
+pub mod telemetry;
+pub mod timestep;
+pub mod tournament;
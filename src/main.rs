@@ -1,87 +1,99 @@
-use macroquad::{prelude::*, rand::gen_range, ui::root_ui};
-
-mod ai;
-mod bounds;
-mod constants;
-mod physics;
-mod player;
+use macroquad::{
+    prelude::*,
+    ui::{root_ui, widgets},
+};
 
-use crate::{
+use pong_clone::{
+    adaptive::AdaptiveAi,
+    ai::AiProfiles,
+    arena::{ArenaState, Edge, EDGES},
+    config::FileWatcher,
     constants::*,
-    physics::{ColliderType, GameObject},
-    player::Player,
+    controller::{KeyboardController, MouseController, PaddleController, TouchController},
+    controls::{Action, Binding, Controls, ACTIONS},
+    events::Side,
+    game::GameState,
+    gamepad::{
+        default_backend, GamepadController, GamepadEvent, GamepadId, SharedGamepads, StickAxis,
+    },
+    physics::{ColliderType, GameObject, PhysicsConfig},
+    rules::Rulesets,
+    telemetry::{save_logs, DecisionLog},
 };
 
+// Listed after the profiles from the file when cycling through AIs.
+const ADAPTIVE_PROFILE: &str = "Adaptive";
+const AI_LOG_PATH: &str = "ai_log.json";
+
+// The four-player mode, with the AI profile playing each edge while nobody takes it over and
+// how each edge is played, both in the order of `EDGES`.
+struct FourPlayers {
+    arena: ArenaState,
+    profiles: [String; 4],
+    modes: [ControlMode; 4],
+}
+
+// The controls being edited on the rebinding screen, and the action waiting for a new binding.
+struct Rebinding {
+    draft: Controls,
+    waiting: Option<Action>,
+    message: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ControlMode {
+    Ai,
+    Pointer,
+    Keyboard,
+    Gamepad(GamepadId),
+}
+
 #[macroquad::main(window_conf)]
 async fn main() {
     println!("_________New game_________");
 
-    rand::srand(macroquad::miniquad::date::now() as _);
-
-    let top_bound = GameObject::from_pos(
-        BOUNDS.x,
-        BOUNDS.y - BOUNDS_THICKNESS,
-        ColliderType::Rectangle(BOUNDS.w, BOUNDS_THICKNESS),
-    );
-    let bottom_bound = GameObject::from_pos(
-        BOUNDS.x,
-        BOUNDS.h,
-        ColliderType::Rectangle(BOUNDS.w, BOUNDS_THICKNESS),
-    );
-
-    let left_bound = GameObject::from_pos(
-        BOUNDS.x - BOUNDS_THICKNESS,
-        BOUNDS.y,
-        ColliderType::Rectangle(BOUNDS_THICKNESS, BOUNDS.h),
-    );
-    let right_bound = GameObject::from_pos(
-        BOUNDS.w,
-        BOUNDS.y,
-        ColliderType::Rectangle(BOUNDS_THICKNESS, BOUNDS.h),
+    let seed = seed_from_args().unwrap_or((macroquad::miniquad::date::now() * 1000.0) as u64);
+    println!("Seed: {}", seed);
+
+    let mut profiles = AiProfiles::load(AI_PROFILES_PATH).unwrap_or_else(|error| {
+        eprintln!("Using default AI profiles, {}", error);
+        AiProfiles::default()
+    });
+    let mut profiles_watcher = FileWatcher::new(AI_PROFILES_PATH);
+    let mut profile_left = profiles.profiles[0].name.clone();
+    let mut profile_right = profiles.next_after(&profile_left).name.clone();
+
+    let mut state = GameState::new(
+        ai_controller(&profiles, &profile_left),
+        ai_controller(&profiles, &profile_right),
+        seed,
     );
 
-    let paddle_left = GameObject::from_pos(
-        BOUNDS.x + PLAYER_PADDING,
-        BOUNDS.center().y - PLAYER_HEIGHT / 2.0,
-        ColliderType::Rectangle(PLAYER_WIDTH, PLAYER_HEIGHT),
-    );
-    let paddle_right = GameObject::from_pos(
-        BOUNDS.w - PLAYER_PADDING - PLAYER_WIDTH,
-        BOUNDS.center().y - PLAYER_HEIGHT / 2.0,
-        ColliderType::Rectangle(PLAYER_WIDTH, PLAYER_HEIGHT),
-    );
+    let rulesets = Rulesets::load(RULES_PATH).unwrap_or_else(|error| {
+        eprintln!("Using default rules, {}", error);
+        Rulesets::default()
+    });
+    state.set_rules(rulesets.rulesets[0].clone());
 
-    let mut player_left = Player::new(
-        "Player 1",
-        paddle_left,
-        BOUNDS,
-        PLAYER_VELOCITY.into(),
-        PLAYER_ACCELERATION.into(),
-    );
-    let mut player_right = Player::new(
-        "Player 2",
-        paddle_right,
-        BOUNDS,
-        PLAYER_VELOCITY.into(),
-        PLAYER_ACCELERATION.into(),
-    );
+    let mut controls = Controls::load(CONTROLS_PATH).unwrap_or_else(|error| {
+        eprintln!("Using default controls, {}", error);
+        Controls::default()
+    });
 
-    let mut ball = GameObject::from_pos(
-        BOUNDS.center().x - BALL_RADIUS,
-        BOUNDS.center().y - BALL_RADIUS,
-        ColliderType::Circle(BALL_RADIUS),
-    );
+    let gamepads = default_backend();
 
-    let mut score_time = get_time();
-    let mut random_start = match gen_range(0, 2) {
-        0 => -1,
-        _ => 1,
-    };
+    let physics = PhysicsConfig::load(PHYSICS_CONFIG_PATH).unwrap_or_else(|error| {
+        eprintln!("Using default physics, {}", error);
+        PhysicsConfig::default()
+    });
+    state.apply_physics(&physics);
 
-    let mut game_paused = false;
-    let mut show_prediction = false;
-    let mut mouse_control = false;
-    let mut keyboard_control = false;
+    let mut show_ai_debugger = false;
+    let mut show_ai_status = false;
+    let mut control_left = ControlMode::Ai;
+    let mut control_right = ControlMode::Ai;
+    let mut rebinding: Option<Rebinding> = None;
+    let mut four_players: Option<FourPlayers> = None;
 
     let mut camera = Camera2D::from_display_rect(Rect::new(
         BOUNDS.x - 1.0,
@@ -90,9 +102,6 @@ async fn main() {
         BOUNDS.h + 2.0,
     ));
 
-    let mut ai_left = SARAH;
-    let mut ai_right = RAPHAEL;
-
     loop {
         let game_position = BOUNDS.screen_offset();
         let game_size = BOUNDS.screen_size();
@@ -108,166 +117,840 @@ async fn main() {
 
         let frame_time = get_frame_time();
 
-        if is_key_pressed(KeyCode::Escape) {
-            game_paused ^= true;
+        // Gamepads are polled every frame whatever is on screen, as that is when their sticks
+        // update. Unplugged ones hand their paddle back to the AI.
+        let gamepad_events = gamepads.borrow_mut().poll();
+        for &event in &gamepad_events {
+            match event {
+                GamepadEvent::Connected(id) => {
+                    println!("Gamepad {} connected", gamepad_name(&gamepads, id));
+                }
+                GamepadEvent::Disconnected(id) => {
+                    println!("Gamepad {} disconnected", id);
+                    for (side, control, profile) in [
+                        (Side::Left, &mut control_left, &profile_left),
+                        (Side::Right, &mut control_right, &profile_right),
+                    ] {
+                        if *control == ControlMode::Gamepad(id) {
+                            *control = ControlMode::Ai;
+                            state.set_controller(side, ai_controller(&profiles, profile));
+                        }
+                    }
+                }
+            }
+        }
+
+        if profiles_watcher.changed() {
+            match AiProfiles::load(AI_PROFILES_PATH) {
+                Ok(reloaded) => {
+                    profiles = reloaded;
+                    retune_ais(
+                        [&mut state.controller_left, &mut state.controller_right],
+                        &profiles,
+                    );
+                    if let Some(four) = &mut four_players {
+                        let seats = four.arena.seats.iter_mut();
+                        retune_ais(seats.map(|seat| &mut seat.controller), &profiles);
+                    }
+                    println!("Reloaded AI profiles");
+                }
+                Err(error) => eprintln!("Keeping previous AI profiles, {}", error),
+            }
         }
 
-        if !game_paused {
-            let reset_button = root_ui().button(vec2(0.0, 0.0), "Reset ball");
-            let show_prediction_button = root_ui().button(vec2(0.0, 20.0), "Show ball prediction");
+        if let Some(screen) = &mut rebinding {
+            set_cursor_grab(false);
+            show_mouse(true);
+            if let Some(saved) = update_rebinding(screen) {
+                if let Some(draft) = saved {
+                    controls = draft;
+                    for (side, mode) in [(Side::Left, control_left), (Side::Right, control_right)] {
+                        if mode == ControlMode::Keyboard {
+                            state.set_controller(side, keyboard_controller(side, &controls));
+                        }
+                    }
+                }
+                rebinding = None;
+            }
+            draw_game(&state);
+            next_frame().await;
+            continue;
+        }
 
-            if show_prediction_button {
-                show_prediction ^= true;
+        if let Some(four) = &mut four_players {
+            let events = &gamepad_events;
+            if !update_four_players(four, &profiles, &controls, &gamepads, events, frame_time) {
+                four_players = None;
+            }
+            if let Some(four) = &four_players {
+                draw_arena(&four.arena);
             }
+            next_frame().await;
+            continue;
+        }
 
-            //___SCORING___//
+        if controls.is_pressed(Action::Pause) {
+            state.clock.paused ^= true;
+        }
+
+        if !state.clock.paused {
+            let reset_button = root_ui().button(vec2(0.0, 0.0), "Reset ball");
+            let show_ai_debugger_button = root_ui().button(vec2(0.0, 20.0), "Show AI debugger");
 
-            if ball.check_collisions(&left_bound).is_some() {
-                player_right.scored();
-                score_time = get_time();
+            let show_ai_status_button = root_ui().button(vec2(0.0, 40.0), "Show AI status");
+
+            if show_ai_debugger_button {
+                show_ai_debugger ^= true;
+            }
+            if show_ai_status_button {
+                show_ai_status ^= true;
             }
 
-            if ball.check_collisions(&right_bound).is_some() {
-                player_left.scored();
-                score_time = get_time();
+            for (side, y) in [(Side::Left, 60.0), (Side::Right, 80.0)] {
+                let (profile, control) = match side {
+                    Side::Left => (&mut profile_left, control_left),
+                    Side::Right => (&mut profile_right, control_right),
+                };
+                let label = format!("{:?} AI: {}", side, profile);
+                if root_ui().button(vec2(0.0, y), label.as_str()) {
+                    *profile = next_profile(&profiles, profile);
+                    if control == ControlMode::Ai {
+                        state.set_controller(side, ai_controller(&profiles, profile));
+                    }
+                }
             }
 
-            if reset_button {
-                score_time = get_time();
+            let rules_label = format!("Rules: {}", state.rules.name);
+            if root_ui().button(vec2(0.0, 100.0), rules_label.as_str()) {
+                state.set_rules(rulesets.next_after(&state.rules.name).clone());
             }
 
-            if score_time != 0.0 {
-                ball.position = BOUNDS.center() - Vec2::from(BALL_SIZE) / 2.0;
-                ball.velocity = vec2(0.0, 0.0);
+            let modes = (control_left, control_right);
 
-                if get_time() > score_time + 1.0 {
-                    ball.velocity = vec2((random_start as f32) * 1000.0, gen_range(-400.0, 400.0));
-                    random_start *= -1;
-                    score_time = 0.0;
-                }
+            let versus = control_left != ControlMode::Ai && control_right != ControlMode::Ai;
+            let versus_label = if versus {
+                "Play against AI"
+            } else {
+                "Two players"
+            };
+            if root_ui().button(vec2(0.0, 120.0), versus_label) {
+                let mode = if versus {
+                    ControlMode::Ai
+                } else if touches().is_empty() {
+                    ControlMode::Keyboard
+                } else {
+                    ControlMode::Pointer
+                };
+                control_left = mode;
+                control_right = mode;
+                state.rematch();
             }
 
-            //___PHYSICS___//
-
-            ball.handle_bounces(
-                vec![
-                    &player_left.object,
-                    &player_right.object,
-                    &top_bound,
-                    &bottom_bound,
-                ],
-                frame_time,
-            );
+            if root_ui().button(vec2(0.0, 140.0), "Four players") {
+                let edge_profiles = [
+                    profile_left.clone(),
+                    next_profile(&profiles, &profile_left),
+                    profile_right.clone(),
+                    next_profile(&profiles, &profile_right),
+                ];
+                let controllers = edge_profiles
+                    .clone()
+                    .map(|profile| ai_controller(&profiles, &profile));
+                let mut arena = ArenaState::new(controllers, ARENA_LIVES, seed);
+                arena.apply_physics(&physics);
+                four_players = Some(FourPlayers {
+                    arena,
+                    profiles: edge_profiles,
+                    modes: [ControlMode::Ai; 4],
+                });
+            }
 
-            //___PLAYER INPUTS___//
+            if root_ui().button(vec2(0.0, 160.0), "Controls") {
+                rebinding = Some(Rebinding {
+                    draft: controls.clone(),
+                    waiting: None,
+                    message: None,
+                });
+            }
 
-            if is_mouse_button_pressed(MouseButton::Left) {
-                if BOUNDS.convert_to_local(mouse_position().into()).y
-                    > player_left.object.collider.rect.top()
-                    && BOUNDS.convert_to_local(mouse_position().into()).y
-                        < player_left.object.collider.rect.bottom()
-                    || mouse_control
-                {
-                    mouse_control ^= true;
-                }
-                keyboard_control = false;
-                if mouse_control {
-                    score_time = get_time();
+            let connected = gamepads.borrow().connected();
+            let assigned = |control| matches!(control, ControlMode::Gamepad(_));
+            if !connected.is_empty() || assigned(control_left) || assigned(control_right) {
+                for (side, y) in [(Side::Left, 180.0), (Side::Right, 200.0)] {
+                    let (control, other) = match side {
+                        Side::Left => (&mut control_left, control_right),
+                        Side::Right => (&mut control_right, control_left),
+                    };
+                    let label = match *control {
+                        ControlMode::Gamepad(id) => {
+                            format!("{:?} gamepad: {}", side, gamepad_name(&gamepads, id))
+                        }
+                        _ => format!("{:?} gamepad: none", side),
+                    };
+                    if root_ui().button(vec2(0.0, y), label.as_str()) {
+                        *control = next_gamepad(&connected, *control, &[other]);
+                    }
                 }
             }
 
-            if is_mouse_button_pressed(MouseButton::Middle) {
-                mouse_control = false;
-                keyboard_control ^= true;
+            if show_ai_debugger {
+                start_ai_logs(&mut state);
+                if root_ui().button(vec2(0.0, 220.0), "Export AI log") {
+                    export_ai_logs(&state);
+                }
             }
 
-            if mouse_control {
-                set_cursor_grab(true);
-                show_mouse(false);
-                player_left.mouse_control(frame_time);
-            } else {
-                set_cursor_grab(false);
-                show_mouse(true);
-            }
+            //___PLAYER INPUTS___//
 
-            if keyboard_control {
-                player_left.keyboard_control(KeyCode::W, KeyCode::S, frame_time);
+            if reset_button || controls.is_pressed(Action::Serve) {
+                state.reset_ball();
             }
 
-            //___AIs___//
+            if state.winner.is_some() {
+                let rematch_position = vec2(screen_width() / 2.0, screen_height() / 2.0 + 40.0);
+                if root_ui().button(rematch_position, "Rematch") {
+                    state.rematch();
+                }
+            }
 
-            if !mouse_control && !keyboard_control {
-                ai_left.logic.observe(
-                    player_left.object.position,
-                    ball.check_collisions_vec(vec![&player_right.object]),
-                    ball.position,
-                    ball.velocity,
-                );
-                player_left.ai_control(&mut ai_left, frame_time);
+            if controls.is_pressed(Action::TogglePointer) {
+                let pointer = pointer_position();
+                let side = side_at(pointer);
+                let control = match side {
+                    Side::Left => &mut control_left,
+                    Side::Right => &mut control_right,
+                };
+                let rect = state.player(side).object.collider.rect;
+                if *control == ControlMode::Pointer {
+                    *control = ControlMode::Ai;
+                } else if pointer.y > rect.top() && pointer.y < rect.bottom() {
+                    *control = ControlMode::Pointer;
+                    state.reset_ball();
+                }
             }
 
-            ai_right.logic.observe(
-                player_right.object.position,
-                ball.check_collisions_vec(vec![&player_left.object]),
-                ball.position,
-                ball.velocity,
-            );
-            player_right.ai_control(&mut ai_right, frame_time);
-
-            if show_prediction {
-                if let Some(predicted_position) = ai_left.logic.predicted_position {
-                    GameObject::from_pos(
-                        predicted_position.x,
-                        predicted_position.y,
-                        ColliderType::Circle(BALL_RADIUS),
-                    )
-                    .show_object(WHITE);
+            let pointer_side = side_at(pointer_position());
+            for side in [Side::Left, Side::Right] {
+                let toggled = controls.is_pressed(Action::ToggleKeyboard(side))
+                    || (side == pointer_side
+                        && controls.is_pressed(Action::ToggleKeyboardAtPointer));
+                if !toggled {
+                    continue;
+                }
+                let control = match side {
+                    Side::Left => &mut control_left,
+                    Side::Right => &mut control_right,
+                };
+                if *control == ControlMode::Keyboard {
+                    *control = ControlMode::Ai;
+                } else {
+                    *control = ControlMode::Keyboard;
                 }
+            }
 
-                if let Some(predicted_position) = ai_right.logic.predicted_position {
-                    GameObject::from_pos(
-                        predicted_position.x,
-                        predicted_position.y,
-                        ColliderType::Circle(BALL_RADIUS),
-                    )
-                    .show_object(WHITE);
+            // Pointers are reseated whenever controls change, to split the field between them
+            // while both paddles follow one.
+            if (control_left, control_right) != modes {
+                let shared_pointer =
+                    control_left == ControlMode::Pointer && control_right == ControlMode::Pointer;
+                for (side, mode, previous, profile) in [
+                    (Side::Left, control_left, modes.0, &profile_left),
+                    (Side::Right, control_right, modes.1, &profile_right),
+                ] {
+                    if mode == previous && mode != ControlMode::Pointer {
+                        continue;
+                    }
+                    let controller = match mode {
+                        ControlMode::Ai => ai_controller(&profiles, profile),
+                        ControlMode::Pointer => pointer_controller(
+                            &controls.player(side).name,
+                            shared_pointer.then_some(side),
+                        ),
+                        ControlMode::Keyboard => keyboard_controller(side, &controls),
+                        ControlMode::Gamepad(id) => Box::new(GamepadController::new(
+                            &controls.player(side).name,
+                            id,
+                            gamepads.clone(),
+                            controls.gamepad,
+                            StickAxis::Vertical,
+                        )),
+                    };
+                    state.set_controller(side, controller);
                 }
             }
+
+            let pointer_control =
+                control_left == ControlMode::Pointer || control_right == ControlMode::Pointer;
+            set_cursor_grab(pointer_control);
+            show_mouse(!pointer_control);
+
+            state.poll_controllers();
+
+            //___SIMULATION___//
+
+            state.advance(frame_time);
         } else {
             set_cursor_grab(false);
             show_mouse(true);
             let resume_button =
                 root_ui().button(vec2(screen_width() / 2.0, screen_height() / 2.0), "Resume");
             if resume_button {
-                game_paused = false;
+                state.clock.paused = false;
+            }
+        }
+
+        draw_game(&state);
+        if show_ai_debugger {
+            draw_ai_debugger(&state);
+        }
+        if show_ai_status {
+            draw_ai_status(&state);
+        }
+
+        next_frame().await;
+    }
+}
+
+fn seed_from_args() -> Option<u64> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--seed" {
+            match args.next().map(|seed| seed.parse()) {
+                Some(Ok(seed)) => return Some(seed),
+                _ => eprintln!("--seed expects an unsigned integer, using a random seed"),
+            }
+        }
+    }
+    None
+}
+
+fn pointer_position() -> Vec2 {
+    let position = match touches().last() {
+        Some(touch) => touch.position,
+        None => mouse_position().into(),
+    };
+    BOUNDS.convert_to_local(position)
+}
+
+fn side_at(position: Vec2) -> Side {
+    if position.x < BOUNDS.center().x {
+        Side::Left
+    } else {
+        Side::Right
+    }
+}
+
+fn next_profile(profiles: &AiProfiles, name: &str) -> String {
+    if name == ADAPTIVE_PROFILE {
+        return profiles.profiles[0].name.clone();
+    }
+    let next = profiles.next_after(name);
+    if profiles.get(name).is_some() && next.name == profiles.profiles[0].name {
+        ADAPTIVE_PROFILE.to_owned()
+    } else {
+        next.name.clone()
+    }
+}
+
+// Falls back to the first profile when the selected one was removed from the file.
+fn ai_controller(profiles: &AiProfiles, name: &str) -> Box<dyn PaddleController> {
+    if name == ADAPTIVE_PROFILE {
+        match AdaptiveAi::new(profiles) {
+            Some(adaptive) => return Box::new(adaptive),
+            None => eprintln!("The adaptive AI needs the profiles named in [adaptive]"),
+        }
+    }
+    profiles
+        .get(name)
+        .unwrap_or(&profiles.profiles[0])
+        .controller()
+}
+
+// Applies reloaded profiles to the AIs currently playing, without interrupting the rally.
+fn retune_ais<'a>(
+    controllers: impl IntoIterator<Item = &'a mut Box<dyn PaddleController>>,
+    profiles: &AiProfiles,
+) {
+    for controller in controllers {
+        if let Some(ai) = controller.ai_mut() {
+            if let Some(profile) = profiles.get(&ai.name) {
+                ai.apply_profile(profile);
+            }
+        }
+    }
+}
+
+fn pointer_controller(name: &str, zone: Option<Side>) -> Box<dyn PaddleController> {
+    if touches().is_empty() {
+        Box::new(MouseController::new(name, zone))
+    } else {
+        Box::new(TouchController::new(name, zone))
+    }
+}
+
+fn keyboard_controller(side: Side, controls: &Controls) -> Box<dyn PaddleController> {
+    let player = controls.player(side);
+    Box::new(KeyboardController::new(
+        &player.name,
+        player.up,
+        player.down,
+    ))
+}
+
+fn gamepad_name(gamepads: &SharedGamepads, id: GamepadId) -> String {
+    gamepads
+        .borrow()
+        .name(id)
+        .unwrap_or_else(|| format!("#{}", id))
+}
+
+// The connected gamepad after the one `control` plays with, skipping the ones `others` play with,
+// and back to the AI after the last one.
+fn next_gamepad(
+    connected: &[GamepadId],
+    control: ControlMode,
+    others: &[ControlMode],
+) -> ControlMode {
+    let mut free = connected
+        .iter()
+        .copied()
+        .filter(|&id| !others.contains(&ControlMode::Gamepad(id)));
+    let next = match control {
+        ControlMode::Gamepad(current) => free.skip_while(|&id| id != current).nth(1),
+        _ => free.next(),
+    };
+    next.map_or(ControlMode::Ai, ControlMode::Gamepad)
+}
+
+// Runs one frame of the four-player mode, returning false once the players go back to two.
+// Every edge can be played by the AI, the pointer or a gamepad, and the left and right ones by
+// the keyboard too, with the keys of the two-player game.
+fn update_four_players(
+    four: &mut FourPlayers,
+    profiles: &AiProfiles,
+    controls: &Controls,
+    gamepads: &SharedGamepads,
+    gamepad_events: &[GamepadEvent],
+    frame_time: f32,
+) -> bool {
+    // The cursor stays free even while edges follow it, to reach the buttons choosing who plays.
+    set_cursor_grab(false);
+    show_mouse(true);
+
+    let arena = &mut four.arena;
+
+    // Unplugged gamepads hand their edge back to the AI, even while paused.
+    for &event in gamepad_events {
+        let GamepadEvent::Disconnected(id) = event else {
+            continue;
+        };
+        for (index, edge) in EDGES.into_iter().enumerate() {
+            if four.modes[index] == ControlMode::Gamepad(id) {
+                four.modes[index] = ControlMode::Ai;
+                arena.set_controller(edge, ai_controller(profiles, &four.profiles[index]));
+            }
+        }
+    }
+
+    if controls.is_pressed(Action::Pause) {
+        arena.clock.paused ^= true;
+    }
+    if arena.clock.paused {
+        let resume_position = vec2(screen_width() / 2.0, screen_height() / 2.0);
+        if root_ui().button(resume_position, "Resume") {
+            arena.clock.paused = false;
+        }
+        return true;
+    }
+
+    if root_ui().button(vec2(0.0, 0.0), "Reset ball") || controls.is_pressed(Action::Serve) {
+        arena.reset_ball();
+    }
+    if root_ui().button(vec2(0.0, 20.0), "Two players") {
+        return false;
+    }
+    if arena.winner.is_some() {
+        let rematch_position = vec2(screen_width() / 2.0, screen_height() / 2.0 + 40.0);
+        if root_ui().button(rematch_position, "Rematch") {
+            arena.rematch();
+        }
+    }
+
+    let connected = gamepads.borrow().connected();
+    let previous = four.modes;
+
+    for (index, edge) in EDGES.into_iter().enumerate() {
+        let side = seat_side(edge);
+        let mode = four.modes[index];
+        let label = match mode {
+            ControlMode::Ai => format!("{:?}: AI {}", edge, four.profiles[index]),
+            ControlMode::Keyboard => format!("{:?}: keyboard", edge),
+            ControlMode::Pointer => format!("{:?}: pointer", edge),
+            ControlMode::Gamepad(id) => {
+                format!("{:?}: gamepad {}", edge, gamepad_name(gamepads, id))
+            }
+        };
+        if root_ui().button(vec2(0.0, 40.0 + index as f32 * 20.0), label.as_str()) {
+            four.modes[index] = match mode {
+                ControlMode::Ai if side.is_some() => ControlMode::Keyboard,
+                ControlMode::Ai | ControlMode::Keyboard => ControlMode::Pointer,
+                ControlMode::Pointer | ControlMode::Gamepad(_) => {
+                    let mut others = four.modes;
+                    others[index] = ControlMode::Ai;
+                    next_gamepad(&connected, mode, &others)
+                }
+            };
+        }
+
+        if side.is_some_and(|side| controls.is_pressed(Action::ToggleKeyboard(side))) {
+            four.modes[index] = if four.modes[index] == ControlMode::Keyboard {
+                ControlMode::Ai
+            } else {
+                ControlMode::Keyboard
+            };
+        }
+    }
+
+    for (index, edge) in EDGES.into_iter().enumerate() {
+        let mode = four.modes[index];
+        if mode == previous[index] {
+            continue;
+        }
+        let name = match seat_side(edge) {
+            Some(side) => controls.player(side).name.as_str(),
+            None => edge.default_name(),
+        };
+        // The stick moves a paddle the way it is pushed, sideways for the top and bottom ones.
+        let axis = if edge.is_horizontal() {
+            StickAxis::Horizontal
+        } else {
+            StickAxis::Vertical
+        };
+        let controller = match (mode, seat_side(edge)) {
+            (ControlMode::Keyboard, Some(side)) => keyboard_controller(side, controls),
+            (ControlMode::Pointer, _) => pointer_controller(name, None),
+            (ControlMode::Gamepad(id), _) => Box::new(GamepadController::new(
+                name,
+                id,
+                gamepads.clone(),
+                controls.gamepad,
+                axis,
+            )),
+            _ => ai_controller(profiles, &four.profiles[index]),
+        };
+        arena.set_controller(edge, controller);
+    }
+
+    arena.poll_controllers();
+    arena.advance(frame_time);
+    true
+}
+
+// The side whose keys play an edge of the arena, only the left and right edges have some.
+fn seat_side(edge: Edge) -> Option<Side> {
+    match edge {
+        Edge::Left => Some(Side::Left),
+        Edge::Right => Some(Side::Right),
+        Edge::Top | Edge::Bottom => None,
+    }
+}
+
+// Runs one frame of the rebinding screen. Returns None while it stays open, then Some with the
+// saved controls, or Some(None) if it was closed without saving.
+fn update_rebinding(screen: &mut Rebinding) -> Option<Option<Controls>> {
+    let x = screen_width() / 2.0 - 240.0;
+    let top = 80.0;
+    root_ui().label(vec2(x, top - 40.0), "Controls");
+
+    // Any key can be bound, Escape included, so waiting is cancelled with a button instead. A
+    // click on it is left to the button, which fires on release, rather than bound.
+    let y = top + ACTIONS.len() as f32 * 24.0 + 20.0;
+    let cancel = Rect::new(x, y, 80.0, 24.0);
+    if let Some(action) = screen.waiting {
+        let on_cancel = cancel.contains(mouse_position().into());
+        let pressed = Binding::pressed()
+            .filter(|binding| !(on_cancel && matches!(binding, Binding::Mouse(_))));
+        if let Some(binding) = pressed {
+            *screen.draft.binding_mut(action) = binding;
+            screen.waiting = None;
+            screen.message = None;
+        }
+    }
+
+    let conflicts = screen.draft.conflicts();
+    for (row, action) in ACTIONS.into_iter().enumerate() {
+        let y = top + row as f32 * 24.0;
+        root_ui().label(vec2(x, y), &action.label());
+
+        let binding = screen.draft.binding(action).name();
+        if screen.waiting == Some(action) {
+            root_ui().label(vec2(x + 240.0, y), "Press a key or mouse button");
+        } else if screen.waiting.is_some() {
+            root_ui().label(vec2(x + 240.0, y), &binding);
+        } else if root_ui().button(vec2(x + 240.0, y), binding.as_str()) {
+            screen.waiting = Some(action);
+        }
+
+        let clashes: Vec<String> = conflicts
+            .iter()
+            .filter_map(|&(first, second)| {
+                if action == first {
+                    Some(second.label())
+                } else if action == second {
+                    Some(first.label())
+                } else {
+                    None
+                }
+            })
+            .collect();
+        if !clashes.is_empty() {
+            let text = format!("Also bound to {}", clashes.join(", "));
+            root_ui().label(vec2(x + 360.0, y), &text);
+        }
+    }
+
+    if let Some(message) = &screen.message {
+        root_ui().label(vec2(x, y + 30.0), message);
+    }
+    if screen.waiting.is_some() {
+        if widgets::Button::new("Cancel")
+            .position(cancel.point())
+            .size(cancel.size())
+            .ui(&mut root_ui())
+        {
+            screen.waiting = None;
+        }
+        return None;
+    }
+
+    if root_ui().button(vec2(x, y), "Save") {
+        if conflicts.is_empty() {
+            match screen.draft.save(CONTROLS_PATH) {
+                Ok(()) => return Some(Some(screen.draft.clone())),
+                Err(error) => screen.message = Some(format!("Could not save, {}", error)),
             }
+        } else {
+            screen.message = Some("Bind every action to a different key to save".to_owned());
+        }
+    }
+    if root_ui().button(vec2(x + 80.0, y), "Defaults") {
+        let defaults = Controls::default();
+        for action in ACTIONS {
+            *screen.draft.binding_mut(action) = defaults.binding(action);
+        }
+        screen.message = None;
+    }
+    if root_ui().button(vec2(x + 180.0, y), "Cancel") {
+        return Some(None);
+    }
+    None
+}
+
+// AIs log their decisions from the moment the debugger is opened, including AIs swapped in later.
+fn start_ai_logs(state: &mut GameState) {
+    for side in [Side::Left, Side::Right] {
+        if let Some(ai) = state.controller_mut(side).ai_mut() {
+            ai.logic.log.get_or_insert_with(DecisionLog::new);
+        }
+    }
+}
+
+fn export_ai_logs(state: &GameState) {
+    let logs: Vec<(&str, &DecisionLog)> = [Side::Left, Side::Right]
+        .into_iter()
+        .filter_map(|side| state.controller(side).ai())
+        .filter_map(|ai| Some((ai.name.as_ref(), ai.logic.log.as_ref()?)))
+        .collect();
+    match save_logs(AI_LOG_PATH, &logs) {
+        Ok(()) => println!("AI log written to {}", AI_LOG_PATH),
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+// Draws what each AI believes: the predicted flight of the ball, where on the paddle it wants to
+// meet it, and the numbers behind the decision.
+fn draw_ai_debugger(state: &GameState) {
+    for (side, x) in [
+        (Side::Left, BOUNDS.x + 20.0),
+        (Side::Right, BOUNDS.center().x + 20.0),
+    ] {
+        let Some(ai) = state.controller(side).ai() else {
+            continue;
+        };
+        let logic = &ai.logic;
+        let Some(predicted_position) = logic.predicted_position else {
+            continue;
+        };
+
+        let obstacles = [
+            &state.top_bound,
+            &state.bottom_bound,
+            &state.player(side.opponent()).object,
+        ];
+        let offset = vec2(BALL_RADIUS, BALL_RADIUS);
+        for segment in logic
+            .trajectory(&state.ball, &obstacles, state.player(side).bounds)
+            .windows(2)
+        {
+            let (from, to) = (segment[0] + offset, segment[1] + offset);
+            draw_line(from.x, from.y, to.x, to.y, 2.0, GRAY);
+        }
+        GameObject::from_pos(
+            predicted_position.x,
+            predicted_position.y,
+            ColliderType::Circle(BALL_RADIUS),
+        )
+        .show_object(WHITE);
+
+        let paddle = &state.player(side).object.collider.rect;
+        let hit_y = paddle.y + paddle.h * logic.hit_position;
+        draw_line(paddle.x - 6.0, hit_y, paddle.right() + 6.0, hit_y, 3.0, RED);
+
+        let lines = [
+            format!(
+                "Reaction in {:.2} s",
+                logic.reaction_remaining(state.clock.now())
+            ),
+            format!("Accuracy variation x{:.2}", logic.accuracy_variation),
+            format!("Hit position {:.2}", logic.hit_position),
+            format!("Tiredness {:.2}", logic.tiredness()),
+        ];
+        for (line, text) in lines.iter().enumerate() {
+            draw_text(text, x, 140.0 + line as f32 * 22.0, 24.0, GRAY);
+        }
+    }
+}
+
+fn draw_game(state: &GameState) {
+    for (player, x) in [
+        (&state.player_left, (BOUNDS.center().x + BOUNDS.x) / 2.0),
+        (&state.player_right, (BOUNDS.w + BOUNDS.center().x) / 2.0),
+    ] {
+        draw_text(&player.name, x, BOUNDS.center().y / 2.0 - 60.0, 30.0, GRAY);
+    }
+    draw_text(
+        &state.player_left.score.to_string(),
+        (BOUNDS.center().x + BOUNDS.x) / 2.0,
+        BOUNDS.center().y / 2.0,
+        60.0,
+        WHITE,
+    );
+    draw_text(
+        &state.player_right.score.to_string(),
+        (BOUNDS.w + BOUNDS.center().x) / 2.0,
+        BOUNDS.center().y / 2.0,
+        60.0,
+        WHITE,
+    );
+
+    if state.rules.games > 1 {
+        for (player, x) in [
+            (&state.player_left, (BOUNDS.center().x + BOUNDS.x) / 2.0),
+            (&state.player_right, (BOUNDS.w + BOUNDS.center().x) / 2.0),
+        ] {
+            let games = format!("Games {}", player.games);
+            draw_text(&games, x, BOUNDS.center().y / 2.0 + 40.0, 30.0, GRAY);
         }
+    }
 
+    if let Some(winner) = state.winner {
+        let text = format!("{} wins!", state.player(winner).name);
+        let size = measure_text(&text, None, 60, 1.0);
+        let center = BOUNDS.center();
         draw_text(
-            &player_left.score.to_string(),
-            (BOUNDS.center().x + BOUNDS.x) / 2.0,
-            BOUNDS.center().y / 2.0,
+            &text,
+            center.x - size.width / 2.0,
+            center.y - 60.0,
             60.0,
             WHITE,
         );
+    } else if state.rules.is_deuce(state.scores()) {
+        let (left, right) = state.scores();
+        let text = if left == right {
+            "Deuce".to_owned()
+        } else {
+            let leader = if left > right {
+                Side::Left
+            } else {
+                Side::Right
+            };
+            format!("Advantage {}", state.player(leader).name)
+        };
+        let size = measure_text(&text, None, 30, 1.0);
+        let x = BOUNDS.center().x - size.width / 2.0;
+        draw_text(&text, x, BOUNDS.center().y / 2.0, 30.0, GRAY);
+    }
+
+    let alpha = state.alpha();
+    state.player_left.object.show_interpolated(alpha, WHITE);
+    state.player_right.object.show_interpolated(alpha, WHITE);
+    state.ball.show_interpolated(alpha, WHITE);
+    show_spin(&state.ball, alpha);
+    state.top_bound.show_object(WHITE);
+    state.bottom_bound.show_object(WHITE);
+}
+
+fn draw_arena(arena: &ArenaState) {
+    let center = BOUNDS.center();
+    for seat in &arena.seats {
+        let (x, y) = match seat.edge {
+            Edge::Left => (BOUNDS.x + 100.0, center.y),
+            Edge::Right => (BOUNDS.w - 260.0, center.y),
+            Edge::Top => (center.x - 80.0, BOUNDS.y + 130.0),
+            Edge::Bottom => (center.x - 80.0, BOUNDS.h - 110.0),
+        };
+        let lives = if seat.is_out() {
+            "Out".to_owned()
+        } else {
+            format!("Lives {}", seat.lives)
+        };
+        draw_text(&seat.player.name, x, y, 30.0, GRAY);
+        draw_text(&lives, x, y + 30.0, 30.0, WHITE);
+
+        if seat.is_out() {
+            seat.goal.show_object(WHITE);
+        } else {
+            seat.player.object.show_interpolated(arena.alpha(), WHITE);
+        }
+    }
+
+    if let Some(winner) = arena.winner {
+        let text = format!("{} wins!", arena.seat(winner).player.name);
+        let size = measure_text(&text, None, 60, 1.0);
         draw_text(
-            &player_right.score.to_string(),
-            (BOUNDS.w + BOUNDS.center().x) / 2.0,
-            BOUNDS.center().y / 2.0,
+            &text,
+            center.x - size.width / 2.0,
+            center.y - 60.0,
             60.0,
             WHITE,
         );
+    }
 
-        player_left.object.show_object(WHITE);
-        player_right.object.show_object(WHITE);
-        ball.show_object(WHITE);
-        top_bound.show_object(WHITE);
-        bottom_bound.show_object(WHITE);
+    arena.ball.show_interpolated(arena.alpha(), WHITE);
+    show_spin(&arena.ball, arena.alpha());
+}
 
-        next_frame().await;
+fn draw_ai_status(state: &GameState) {
+    for (side, x) in [
+        (Side::Left, BOUNDS.x + 20.0),
+        (Side::Right, BOUNDS.center().x + 20.0),
+    ] {
+        let Some(status) = state.controller(side).status() else {
+            continue;
+        };
+        for (line, text) in status.lines().enumerate() {
+            draw_text(text, x, BOUNDS.h - 100.0 + line as f32 * 22.0, 24.0, GRAY);
+        }
     }
 }
 
+fn show_spin(ball: &GameObject, alpha: f32) {
+    let radius = ball.collider.rect.w / 2.0;
+    let center = ball.previous_position.lerp(ball.position, alpha) + vec2(radius, radius);
+    let marker = center + vec2(ball.rotation.cos(), ball.rotation.sin()) * radius * 0.8;
+    draw_line(center.x, center.y, marker.x, marker.y, 2.0, BLACK);
+}
+
 fn window_conf() -> Conf {
     Conf {
         window_title: "Pong Clone".to_owned(),
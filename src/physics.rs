@@ -1,20 +1,24 @@
 use macroquad::prelude::{draw_circle, draw_rectangle, vec2, Color, Rect, Vec2};
+use serde::Deserialize;
 
-use crate::bounds::Bounds;
+use crate::{
+    bounds::Bounds,
+    collision::{
+        circle_circle, circle_rect, rect_rect, sweep_box, sweep_circle_circle, sweep_circle_rect,
+        Contact,
+    },
+    config::{load_toml, ConfigError},
+};
 
-#[derive(Debug)]
+const MAX_BOUNCES_PER_STEP: usize = 4;
+
+#[derive(Debug, Clone)]
 pub enum ColliderType {
     Rectangle(f32, f32),
     Circle(f32),
 }
 
-#[derive(Debug)]
-pub enum CollisionType {
-    Vertical,
-    Horizontal,
-}
-
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Collider {
     pub shape: ColliderType,
     pub rect: Rect,
@@ -39,106 +43,386 @@ impl Collider {
     pub fn update_pos(&mut self, position: Vec2) {
         self.rect.move_to(position);
     }
+
+    pub fn center(&self) -> Vec2 {
+        self.rect.point() + self.rect.size() / 2.0
+    }
+
+    pub fn contact(&self, other: &Self) -> Option<Contact> {
+        match (&self.shape, &other.shape) {
+            (ColliderType::Circle(radius), ColliderType::Rectangle(..)) => {
+                circle_rect(self.center(), *radius, other.rect)
+            }
+            (ColliderType::Rectangle(..), ColliderType::Circle(radius)) => {
+                circle_rect(other.center(), *radius, self.rect).map(Contact::flipped)
+            }
+            (ColliderType::Circle(radius), ColliderType::Circle(other_radius)) => {
+                circle_circle(self.center(), *radius, other.center(), *other_radius)
+            }
+            (ColliderType::Rectangle(..), ColliderType::Rectangle(..)) => {
+                rect_rect(self.rect, other.rect)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Material {
+    pub restitution: f32,
+    pub velocity_transfer: f32,
+    pub deflection: f32,
+    pub max_speed: Option<f32>,
+    #[serde(default)]
+    pub grip: f32,
+    #[serde(default)]
+    pub magnus: f32,
+    #[serde(default)]
+    pub spin_damping: f32,
+}
+
+impl Material {
+    pub const BALL: Self = Self {
+        restitution: 1.0,
+        velocity_transfer: 0.0,
+        deflection: 0.0,
+        max_speed: None,
+        grip: 1.0,
+        magnus: 0.006,
+        spin_damping: 0.5,
+    };
+    pub const PADDLE: Self = Self {
+        restitution: 1.0,
+        velocity_transfer: 1.1,
+        deflection: 0.04,
+        max_speed: None,
+        grip: 0.3,
+        magnus: 0.0,
+        spin_damping: 0.0,
+    };
+    pub const WALL: Self = Self {
+        restitution: 1.0,
+        velocity_transfer: 1.1,
+        deflection: 0.0,
+        max_speed: None,
+        grip: 0.2,
+        magnus: 0.0,
+        spin_damping: 0.0,
+    };
+}
+
+impl Material {
+    fn validate(&self, name: &str) -> Result<(), ConfigError> {
+        if !(0.0..=1.0).contains(&self.grip) {
+            return Err(ConfigError::Invalid(format!(
+                "{}: grip must be between 0 and 1",
+                name
+            )));
+        }
+        if self.restitution < 0.0 {
+            return Err(ConfigError::Invalid(format!(
+                "{}: restitution must not be negative",
+                name
+            )));
+        }
+        if matches!(self.max_speed, Some(max_speed) if max_speed <= 0.0) {
+            return Err(ConfigError::Invalid(format!(
+                "{}: max_speed must be positive",
+                name
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct PhysicsConfig {
+    #[serde(default = "PhysicsConfig::ball")]
+    pub ball: Material,
+    #[serde(default = "PhysicsConfig::paddle")]
+    pub paddle: Material,
+    #[serde(default = "PhysicsConfig::wall")]
+    pub wall: Material,
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        Self {
+            ball: Material::BALL,
+            paddle: Material::PADDLE,
+            wall: Material::WALL,
+        }
+    }
+}
+
+impl PhysicsConfig {
+    pub fn load(path: &str) -> Result<Self, ConfigError> {
+        let config: Self = load_toml(path)?;
+        config.ball.validate("ball")?;
+        config.paddle.validate("paddle")?;
+        config.wall.validate("wall")?;
+        Ok(config)
+    }
+
+    fn ball() -> Material {
+        Material::BALL
+    }
+
+    fn paddle() -> Material {
+        Material::PADDLE
+    }
+
+    fn wall() -> Material {
+        Material::WALL
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
+pub struct Collision {
+    pub index: usize,
+    pub contact: Contact,
+    pub velocity: Vec2,
+}
+
+#[derive(Debug, Clone)]
 pub struct GameObject {
     pub position: Vec2,
+    pub previous_position: Vec2,
     pub velocity: Vec2,
     pub acceleration: Vec2,
+    pub angular_velocity: f32,
+    pub rotation: f32,
     pub collider: Collider,
+    pub material: Material,
     pub is_player: bool,
+    pub is_sensor: bool,
 }
 
 impl GameObject {
     pub fn from_pos(x: f32, y: f32, shape: ColliderType) -> Self {
         Self {
             position: vec2(x, y),
+            previous_position: vec2(x, y),
             velocity: vec2(0.0, 0.0),
             acceleration: vec2(0.0, 0.0),
+            angular_velocity: 0.0,
+            rotation: 0.0,
             collider: Collider::new(x, y, shape),
+            material: Material::WALL,
             is_player: false,
+            is_sensor: false,
         }
     }
 }
 
 impl GameObject {
     pub fn show_object(&self, color: Color) {
+        self.show_object_at(self.position, color);
+    }
+
+    pub fn show_interpolated(&self, alpha: f32, color: Color) {
+        self.show_object_at(self.previous_position.lerp(self.position, alpha), color);
+    }
+
+    fn show_object_at(&self, position: Vec2, color: Color) {
         match self.collider.shape {
-            ColliderType::Rectangle(w, h) => {
-                draw_rectangle(self.position.x, self.position.y, w, h, color)
-            }
+            ColliderType::Rectangle(w, h) => draw_rectangle(position.x, position.y, w, h, color),
             ColliderType::Circle(r) => {
-                draw_circle(self.position.x + r, self.position.y + r, r, color);
+                draw_circle(position.x + r, position.y + r, r, color);
             }
         }
     }
 
+    pub fn store_previous_position(&mut self) {
+        self.previous_position = self.position;
+    }
+
     pub fn move_object(&mut self, frame_time: f32) {
         self.position += self.velocity * frame_time;
         self.collider.update_pos(self.position);
     }
 
-    pub fn check_collisions(&self, object: &Self) -> Option<CollisionType> {
-        let collision_rect = self.collider.rect.intersect(object.collider.rect);
+    pub fn check_collisions(&self, object: &Self) -> Option<Contact> {
+        self.collider.contact(&object.collider)
+    }
+
+    // Returns every object we bounced off, and every sensor we overlap at the end of the move.
+    pub fn handle_bounces(&mut self, with: &[&GameObject], frame_time: f32) -> Vec<Collision> {
+        let mut collisions = Vec::new();
 
-        if let Some(col) = collision_rect {
-            if col.w < col.h {
-                Some(CollisionType::Horizontal)
-            } else {
-                Some(CollisionType::Vertical)
+        self.apply_spin(frame_time);
+        self.collider.update_pos(self.position);
+        for (index, object) in with.iter().enumerate() {
+            if object.is_sensor {
+                continue;
+            }
+            if let Some(contact) = self.resolve_overlap(object) {
+                self.bounce(object, contact);
+                collisions.push(Collision {
+                    index,
+                    contact,
+                    velocity: self.velocity,
+                });
             }
-        } else {
-            None
         }
-    }
 
-    pub fn check_collisions_vec(&self, objects: Vec<&Self>) -> Vec<CollisionType> {
-        let mut collisions = Vec::new();
-        for object in objects {
-            let collision = self.check_collisions(object);
-            if let Some(collision) = collision {
-                collisions.push(collision);
+        let mut remaining_time = frame_time;
+        let mut path_clear = false;
+        for _ in 0..MAX_BOUNCES_PER_STEP {
+            let earliest_hit = with
+                .iter()
+                .enumerate()
+                .filter(|(_, object)| !object.is_sensor)
+                .filter_map(|(index, object)| {
+                    self.time_of_impact(object, remaining_time)
+                        .map(|(time, contact)| (time, contact, index))
+                })
+                .min_by(|a, b| a.0.total_cmp(&b.0));
+
+            match earliest_hit {
+                Some((time, contact, index)) => {
+                    self.move_object(time);
+                    self.bounce(with[index], contact);
+                    collisions.push(Collision {
+                        index,
+                        contact,
+                        velocity: self.velocity,
+                    });
+                    remaining_time -= time;
+                }
+                None => {
+                    path_clear = true;
+                    break;
+                }
+            }
+        }
+        // Once the bounce budget runs out the ball waits at its last contact, as the rest of the
+        // step was never swept.
+        if path_clear {
+            self.move_object(remaining_time);
+        }
+
+        for (index, object) in with.iter().enumerate() {
+            if !object.is_sensor {
+                continue;
+            }
+            if let Some(contact) = self.check_collisions(object) {
+                collisions.push(Collision {
+                    index,
+                    contact,
+                    velocity: self.velocity,
+                });
             }
         }
+
         collisions
     }
 
-    pub fn handle_bounces(&mut self, with: Vec<&GameObject>, frame_time: f32) {
-        self.move_object(frame_time);
-        for object in with {
-            let collision = self.check_collisions(object);
-            if let Some(CollisionType::Vertical) = collision {
-                if self.collider.rect.top() > object.collider.rect.top() {
-                    self.position.y = 2.0 * (object.collider.rect.y + object.collider.rect.h)
-                        - self.collider.rect.y;
-                } else {
-                    self.position.y = 2.0 * (object.collider.rect.y - self.collider.rect.h)
-                        - self.collider.rect.y;
-                }
+    // Magnus effect: spinning curves the trajectory sideways while the spin slowly dies out.
+    pub fn apply_spin(&mut self, frame_time: f32) {
+        let curve = vec2(-self.velocity.y, self.velocity.x) * self.angular_velocity;
+        self.velocity += curve * self.material.magnus * frame_time;
+        self.angular_velocity *= (1.0 - self.material.spin_damping * frame_time).max(0.0);
+        self.rotation += self.angular_velocity * frame_time;
+    }
 
-                self.velocity.y *= -1.0;
-                self.velocity.y += object.velocity.y * 1.1;
-            } else if let Some(CollisionType::Horizontal) = collision {
-                if self.collider.rect.left() > object.collider.rect.left() {
-                    self.position.x = 2.0 * (object.collider.rect.x + object.collider.rect.w)
-                        - self.collider.rect.x;
-                } else {
-                    self.position.x = 2.0 * (object.collider.rect.x - self.collider.rect.w)
-                        - self.collider.rect.x;
-                }
+    // Returns the time of our first contact with the object along our velocity, and the
+    // contact at that time.
+    fn time_of_impact(&self, object: &GameObject, frame_time: f32) -> Option<(f32, Contact)> {
+        let center = self.collider.center();
+        let motion = self.velocity * frame_time;
 
-                self.velocity.x *= -1.0;
-                self.velocity.x += object.velocity.x * 1.1;
+        let hit = match (&self.collider.shape, &object.collider.shape) {
+            (ColliderType::Circle(radius), ColliderType::Rectangle(..)) => {
+                sweep_circle_rect(center, *radius, motion, object.collider.rect)
+            }
+            (ColliderType::Circle(radius), ColliderType::Circle(other_radius)) => {
+                sweep_circle_circle(
+                    center,
+                    radius + other_radius,
+                    motion,
+                    object.collider.center(),
+                )
+            }
+            (ColliderType::Rectangle(..), _) => {
+                let half_size = self.collider.rect.size() / 2.0;
+                let min = object.collider.rect.point() - half_size;
+                let max = min + object.collider.rect.size() + half_size * 2.0;
+                sweep_box(center, motion, min, max)
+            }
+        };
 
-                if object.is_player {
-                    self.velocity.y = ((self.collider.rect.y + self.collider.rect.h / 2.0)
-                        - (object.collider.rect.y + object.collider.rect.h / 2.0))
-                        * self.velocity.x.abs()
-                        / 25.0;
-                }
+        hit.map(|(time, normal)| {
+            let extent = match self.collider.shape {
+                ColliderType::Circle(radius) => radius,
+                ColliderType::Rectangle(..) => (self.collider.rect.size() / 2.0 * normal).length(),
+            };
+            let contact = Contact {
+                normal,
+                depth: 0.0,
+                point: center + motion * time - normal * extent,
+            };
+            (time * frame_time, contact)
+        })
+    }
+
+    // Pushes us out of an object that moved into us, returning the contact normal if we were
+    // moving into it.
+    fn resolve_overlap(&mut self, object: &GameObject) -> Option<Contact> {
+        let contact = self.check_collisions(object)?;
+
+        self.position += contact.normal * contact.depth;
+        self.collider.update_pos(self.position);
+
+        if (self.velocity - object.velocity).dot(contact.normal) < 0.0 {
+            Some(contact)
+        } else {
+            None
+        }
+    }
+
+    fn bounce(&mut self, object: &GameObject, contact: Contact) {
+        let normal = contact.normal;
+        let restitution = self.material.restitution * object.material.restitution;
+
+        self.velocity -= (1.0 + restitution) * self.velocity.dot(normal) * normal;
+        self.velocity += object.velocity.dot(normal) * object.material.velocity_transfer * normal;
+
+        // Paddles send the ball off at an angle set by where it hit along their length, vertical
+        // ones guarding the sides and horizontal ones the top and bottom of a four-player arena.
+        // The ball keeps the speed it bounced with, so a grazing hit doesn't stall it.
+        if object.is_player {
+            let paddle = object.collider.rect;
+            let along = if paddle.h >= paddle.w {
+                vec2(0.0, 1.0)
+            } else {
+                vec2(1.0, 0.0)
+            };
+            if normal.dot(along) == 0.0 {
+                let offset = (contact.point - object.collider.center()).dot(along);
+                let direction = normal + along * offset * object.material.deflection;
+                self.velocity = direction.normalize() * self.velocity.length();
             }
         }
+
+        // Friction at the contact point between the spinning ball and the surface it hit, treating
+        // the ball as a solid disk. The ball's own sliding is left out so bounces don't bleed speed,
+        // only its spin and the motion of the surface matter.
+        if let ColliderType::Circle(radius) = self.collider.shape {
+            let tangent = vec2(-normal.y, normal.x);
+            let grip = self.material.grip * object.material.grip;
+            let slip = -self.angular_velocity * radius - object.velocity.dot(tangent);
+
+            self.velocity -= tangent * grip * slip / 3.0;
+            self.angular_velocity += 2.0 * grip * slip / (3.0 * radius);
+        }
+
+        let max_speed = match (self.material.max_speed, object.material.max_speed) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        if let Some(max_speed) = max_speed {
+            self.velocity = self.velocity.clamp_length_max(max_speed);
+        }
     }
 
     pub fn move_towards(
@@ -174,3 +458,109 @@ impl GameObject {
         self.move_towards(clamped_position, velocity, acceleration, frame_time)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::BALL_RADIUS;
+
+    fn ball(center: Vec2, velocity: Vec2) -> GameObject {
+        let mut ball = GameObject::from_pos(
+            center.x - BALL_RADIUS,
+            center.y - BALL_RADIUS,
+            ColliderType::Circle(BALL_RADIUS),
+        );
+        ball.material = Material::BALL;
+        ball.velocity = velocity;
+        ball
+    }
+
+    fn paddle(x: f32, y: f32, w: f32, h: f32) -> GameObject {
+        let mut paddle = GameObject::from_pos(x, y, ColliderType::Rectangle(w, h));
+        paddle.material = Material::PADDLE;
+        paddle.is_player = true;
+        paddle
+    }
+
+    fn assert_close(actual: Vec2, expected: Vec2) {
+        assert!(
+            (actual - expected).length() < 0.01,
+            "{:?} is not {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn off_center_hit_turns_the_ball_at_its_speed() {
+        // 20 px below the center of the paddle, which spans 400 to 470.
+        let paddle = paddle(100.0, 400.0, 15.0, 70.0);
+        let mut ball = ball(vec2(130.0, 455.0), vec2(-1000.0, 0.0));
+        let collisions = ball.handle_bounces(&[&paddle], 1.0 / 60.0);
+
+        assert_eq!(collisions.len(), 1);
+        let slope = 20.0 * Material::PADDLE.deflection;
+        let expected = vec2(1.0, slope).normalize() * 1000.0;
+        assert_close(collisions[0].velocity, expected);
+        assert_close(ball.velocity, expected);
+    }
+
+    #[test]
+    fn grazing_hit_on_a_flat_paddle_keeps_the_speed() {
+        // Nearly parallel to a paddle guarding the bottom, a fraction of a pixel above it.
+        let paddle = paddle(500.0, 900.0, 70.0, 15.0);
+        let velocity = vec2(50.0, 1.0).normalize() * 1000.0;
+        let mut ball = ball(vec2(536.0, 891.82), velocity);
+        let collisions = ball.handle_bounces(&[&paddle], 1.0 / 60.0);
+
+        assert_eq!(collisions.len(), 1);
+        let offset = collisions[0].contact.point.x - paddle.collider.center().x;
+        let expected = vec2(offset * Material::PADDLE.deflection, -1.0).normalize() * 1000.0;
+        assert_close(collisions[0].velocity, expected);
+    }
+
+    fn wall(x: f32, y: f32, w: f32, h: f32) -> GameObject {
+        GameObject::from_pos(x, y, ColliderType::Rectangle(w, h))
+    }
+
+    #[test]
+    fn fast_ball_hits_a_paddle_thinner_than_its_step() {
+        // 50 px per tick at the game's tick rate, more than the paddle and ball together.
+        let dt = 1.0 / 240.0;
+        let paddle = paddle(100.0, 400.0, 15.0, 70.0);
+        let mut ball = ball(vec2(140.0, 435.0), vec2(-12_000.0, 0.0));
+        let collisions = ball.handle_bounces(&[&paddle], dt);
+
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].contact.normal, vec2(1.0, 0.0));
+        assert!(ball.velocity.x > 0.0);
+        assert!(ball.collider.rect.left() >= 115.0);
+    }
+
+    #[test]
+    fn corner_shot_bounces_off_both_walls_in_one_step() {
+        let left = wall(-20.0, 0.0, 20.0, 1000.0);
+        let top = wall(0.0, -20.0, 1200.0, 20.0);
+        let mut ball = ball(vec2(20.0, 30.0), vec2(-2400.0, -2400.0));
+        let collisions = ball.handle_bounces(&[&left, &top], 1.0 / 60.0);
+
+        let indices: Vec<usize> = collisions.iter().map(|collision| collision.index).collect();
+        assert_eq!(indices, [0, 1]);
+        assert!(ball.velocity.x > 0.0 && ball.velocity.y > 0.0);
+        assert!(ball.collider.rect.left() >= 0.0 && ball.collider.rect.top() >= 0.0);
+    }
+
+    #[test]
+    fn ball_stays_inside_once_the_bounce_budget_runs_out() {
+        // A gap of 30 px leaves the ball 14 px to travel, far less than one step at this speed.
+        let left = wall(80.0, 0.0, 20.0, 1000.0);
+        let right = wall(130.0, 0.0, 20.0, 1000.0);
+        let mut ball = ball(vec2(115.0, 500.0), vec2(-60_000.0, 0.0));
+        let collisions = ball.handle_bounces(&[&left, &right], 1.0 / 240.0);
+
+        assert_eq!(collisions.len(), MAX_BOUNCES_PER_STEP);
+        let rect = ball.collider.rect;
+        assert!(rect.left() >= 100.0 && rect.right() <= 130.0, "{:?}", rect);
+        assert_eq!(ball.position, rect.point());
+    }
+}
@@ -1,18 +1,17 @@
-use std::f32::INFINITY;
-
-use macroquad::prelude::{get_time, is_key_down, mouse_position, touches, vec2, KeyCode, Vec2};
+use macroquad::prelude::Vec2;
 
 use crate::{
-    ai::Ai,
     bounds::Bounds,
-    constants::{BALL_SIZE, PLAYER_HEIGHT, PLAYER_WIDTH},
-    physics::GameObject,
+    controller::PaddleIntent,
+    physics::{GameObject, Material},
 };
 
 pub struct Player {
     pub name: String,
     pub object: GameObject,
-    pub score: u8,
+    // Points in the current game.
+    pub score: u32,
+    pub games: u32,
     pub bounds: Bounds,
     pub max_velocity: Vec2,
     pub max_acceleration: Vec2,
@@ -27,10 +26,12 @@ impl Player {
         max_acceleration: Vec2,
     ) -> Self {
         object.is_player = true;
+        object.material = Material::PADDLE;
         Self {
             name: name.to_owned(),
             object,
             score: 0,
+            games: 0,
             bounds,
             max_velocity,
             max_acceleration,
@@ -40,66 +41,20 @@ impl Player {
 
 impl Player {
     pub fn scored(&mut self) {
-        self.score += 1;
+        self.score = self.score.saturating_add(1);
     }
 
-    pub fn keyboard_control(&mut self, up: KeyCode, down: KeyCode, frame_time: f32) {
-        let towards;
-        if !(is_key_down(up) ^ is_key_down(down)) {
-            towards = self.object.position;
-        } else {
-            if is_key_down(up) {
-                towards = vec2(self.object.position.x, -INFINITY);
-            } else {
-                towards = vec2(self.object.position.x, INFINITY);
-            }
-        }
-        self.object.move_towards_in_bounds(
-            towards,
-            self.max_velocity * 0.5,
-            self.max_acceleration * 0.6,
-            self.bounds,
-            frame_time,
-        );
-    }
-
-    pub fn mouse_control(&mut self, frame_time: f32) {
-        let mut mouse_position_bounds = self.bounds.convert_to_local(mouse_position().into())
-            - self.object.collider.rect.size() / 2.0;
-        if let Some(touch) = touches().last() {
-            mouse_position_bounds = self.bounds.convert_to_local(touch.position)
-                - self.object.collider.rect.size() / 2.0;
-        }
-
-        self.object.move_towards_in_bounds(
-            mouse_position_bounds,
-            self.max_velocity,
-            self.max_acceleration,
-            self.bounds,
-            frame_time,
-        );
-    }
-
-    pub fn ai_control(&mut self, ai: &Ai, frame_time: f32) {
-        self.name = ai.name.to_owned();
-        if let Some(predicted_position) = ai.logic.predicted_position {
-            if get_time() - ai.logic.collision_time >= ai.logic.reaction_time as f64 / 1000.0 {
-                let adjusted_prediction = Vec2::from(BALL_SIZE) / 2.0 + predicted_position
-                    - self.object.collider.rect.size() * ai.logic.hit_position;
-
-                self.object.move_towards_in_bounds(
-                    adjusted_prediction,
-                    self.max_velocity,
-                    self.max_acceleration,
-                    self.bounds,
-                    frame_time,
-                );
-            }
-        } else {
+    pub fn apply_intent(&mut self, intent: PaddleIntent, frame_time: f32) {
+        if let PaddleIntent::MoveTo {
+            target,
+            velocity_scale,
+            acceleration_scale,
+        } = intent
+        {
             self.object.move_towards_in_bounds(
-                self.bounds.center() - Vec2::from((PLAYER_WIDTH, PLAYER_HEIGHT)) / 2.0,
-                self.max_velocity / 3.0,
-                self.max_acceleration,
+                target,
+                self.max_velocity * velocity_scale,
+                self.max_acceleration * acceleration_scale,
                 self.bounds,
                 frame_time,
             );
@@ -0,0 +1,54 @@
+// PCG32 (XSH RR). Small, fast and gives the same sequence on every platform, unlike
+// macroquad's global generator.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+    increment: u64,
+}
+
+pub static SERVE_STREAM: u64 = 0;
+pub static LEFT_AI_STREAM: u64 = 1;
+pub static RIGHT_AI_STREAM: u64 = 2;
+pub static TRAINING_STREAM: u64 = 3;
+pub static EVOLUTION_STREAM: u64 = 4;
+pub static TOP_AI_STREAM: u64 = 5;
+pub static BOTTOM_AI_STREAM: u64 = 6;
+
+const MULTIPLIER: u64 = 6364136223846793005;
+
+impl Rng {
+    pub const fn new(seed: u64, stream: u64) -> Self {
+        let increment = (stream << 1) | 1;
+        let state = increment.wrapping_add(seed);
+        Self {
+            state: state.wrapping_mul(MULTIPLIER).wrapping_add(increment),
+            increment,
+        }
+    }
+}
+
+impl Rng {
+    pub fn next_u32(&mut self) -> u32 {
+        let old_state = self.state;
+        self.state = old_state
+            .wrapping_mul(MULTIPLIER)
+            .wrapping_add(self.increment);
+
+        let xorshifted = (((old_state >> 18) ^ old_state) >> 27) as u32;
+        let rotation = (old_state >> 59) as u32;
+        xorshifted.rotate_right(rotation)
+    }
+
+    // Uniform in [0, 1).
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    pub fn gen_range(&mut self, low: f32, high: f32) -> f32 {
+        low + (high - low) * self.next_f32()
+    }
+
+    pub fn gen_bool(&mut self) -> bool {
+        self.next_u32() & 1 == 1
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{load_toml, ConfigError},
+    events::Side,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServeRule {
+    // Players take turns serving `serves` points each, and every point once the game is at deuce.
+    Rotate,
+    // Whoever won the last point serves.
+    Winner,
+    // Whoever lost the last point serves.
+    Loser,
+}
+
+// How a match is played: games go to `points`, won by `win_by` clear points, and the match to
+// whoever wins most of `games`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rules {
+    pub name: String,
+    pub points: u32,
+    pub win_by: u32,
+    pub games: u32,
+    pub serve: ServeRule,
+    pub serves: u32,
+}
+
+// First to 11 in a single game, serving in turns.
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            name: "Classic".to_owned(),
+            points: 11,
+            win_by: 1,
+            games: 1,
+            serve: ServeRule::Rotate,
+            serves: 1,
+        }
+    }
+}
+
+impl Rules {
+    pub fn first_to(points: u32) -> Self {
+        Self {
+            name: format!("First to {}", points),
+            points,
+            ..Self::default()
+        }
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |reason: &str| {
+            Err(ConfigError::Invalid(format!(
+                "ruleset {:?}: {}",
+                self.name, reason
+            )))
+        };
+
+        if self.name.trim().is_empty() {
+            return invalid("name must not be empty");
+        }
+        if self.points == 0 {
+            return invalid("points must be at least 1");
+        }
+        if self.win_by == 0 {
+            return invalid("win_by must be at least 1");
+        }
+        if self.games == 0 {
+            return invalid("games must be at least 1");
+        }
+        if self.serves == 0 {
+            return invalid("serves must be at least 1");
+        }
+        Ok(())
+    }
+}
+
+impl Rules {
+    pub fn games_to_win(&self) -> u32 {
+        self.games / 2 + 1
+    }
+
+    pub fn game_winner(&self, scores: (u32, u32)) -> Option<Side> {
+        let won = |score: u32, other: u32| score >= self.points && score >= other + self.win_by;
+        if won(scores.0, scores.1) {
+            Some(Side::Left)
+        } else if won(scores.1, scores.0) {
+            Some(Side::Right)
+        } else {
+            None
+        }
+    }
+
+    // Both players are a point away from a game they can't win outright.
+    pub fn is_deuce(&self, scores: (u32, u32)) -> bool {
+        self.win_by > 1 && scores.0.min(scores.1) + 1 >= self.points
+    }
+
+    // Who serves after `scorer` won a point, and how many serves they have left including it.
+    pub fn next_server(
+        &self,
+        server: Side,
+        serves_left: u32,
+        scorer: Side,
+        scores: (u32, u32),
+    ) -> (Side, u32) {
+        match self.serve {
+            ServeRule::Rotate if self.is_deuce(scores) => (server.opponent(), 1),
+            ServeRule::Rotate if serves_left > 1 => (server, serves_left - 1),
+            ServeRule::Rotate => (server.opponent(), self.serves),
+            ServeRule::Winner => (scorer, self.serves),
+            ServeRule::Loser => (scorer.opponent(), self.serves),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rulesets {
+    #[serde(rename = "ruleset")]
+    pub rulesets: Vec<Rules>,
+}
+
+impl Default for Rulesets {
+    fn default() -> Self {
+        Self {
+            rulesets: vec![Rules::default()],
+        }
+    }
+}
+
+impl Rulesets {
+    pub fn load(path: &str) -> Result<Self, ConfigError> {
+        let rulesets: Self = load_toml(path)?;
+        if rulesets.rulesets.is_empty() {
+            return Err(ConfigError::Invalid(format!(
+                "{}: no [[ruleset]] defined",
+                path
+            )));
+        }
+
+        let mut names = HashSet::new();
+        for rules in &rulesets.rulesets {
+            rules.validate()?;
+            if !names.insert(rules.name.as_str()) {
+                return Err(ConfigError::Invalid(format!(
+                    "ruleset {:?} is defined more than once",
+                    rules.name
+                )));
+            }
+        }
+        Ok(rulesets)
+    }
+
+    // The ruleset following `name`, wrapping around, or the first one if `name` is unknown.
+    pub fn next_after(&self, name: &str) -> &Rules {
+        let index = self
+            .rulesets
+            .iter()
+            .position(|rules| rules.name == name)
+            .map_or(0, |index| (index + 1) % self.rulesets.len());
+        &self.rulesets[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table_tennis() -> Rules {
+        Rules {
+            name: "Table tennis".to_owned(),
+            win_by: 2,
+            games: 5,
+            serves: 2,
+            ..Rules::default()
+        }
+    }
+
+    fn load(name: &str, text: &str) -> Result<Rulesets, ConfigError> {
+        let path = std::env::temp_dir().join(format!("{}-{}.toml", name, std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        std::fs::write(&path, text).unwrap();
+        let rulesets = Rulesets::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        rulesets
+    }
+
+    #[test]
+    fn win_by_two_plays_on_past_the_points() {
+        let rules = table_tennis();
+        assert_eq!(rules.game_winner((10, 10)), None);
+        assert_eq!(rules.game_winner((11, 10)), None);
+        assert_eq!(rules.game_winner((11, 11)), None);
+        assert_eq!(rules.game_winner((12, 11)), None);
+        assert_eq!(rules.game_winner((12, 10)), Some(Side::Left));
+        assert_eq!(rules.game_winner((9, 11)), Some(Side::Right));
+
+        let classic = Rules::default();
+        assert_eq!(classic.game_winner((11, 10)), Some(Side::Left));
+    }
+
+    #[test]
+    fn majority_of_the_games_wins_the_match() {
+        let best_of = |games| Rules {
+            games,
+            ..Rules::default()
+        };
+        assert_eq!(best_of(1).games_to_win(), 1);
+        assert_eq!(best_of(3).games_to_win(), 2);
+        assert_eq!(best_of(5).games_to_win(), 3);
+        assert_eq!(best_of(2).games_to_win(), 2);
+        assert_eq!(best_of(4).games_to_win(), 3);
+    }
+
+    #[test]
+    fn serve_rotates_every_point_at_deuce() {
+        let rules = table_tennis();
+        let serve = |server, serves_left, scores| {
+            rules.next_server(server, serves_left, Side::Left, scores)
+        };
+
+        // Two serves each before deuce.
+        assert_eq!(serve(Side::Left, 2, (1, 0)), (Side::Left, 1));
+        assert_eq!(serve(Side::Left, 1, (2, 0)), (Side::Right, 2));
+        assert_eq!(serve(Side::Right, 2, (9, 9)), (Side::Right, 1));
+
+        // One each from 10-10 on, advantage included.
+        assert!(!rules.is_deuce((10, 9)));
+        assert!(rules.is_deuce((10, 10)));
+        assert!(rules.is_deuce((11, 10)));
+        assert_eq!(serve(Side::Right, 2, (10, 10)), (Side::Left, 1));
+        assert_eq!(serve(Side::Left, 1, (11, 10)), (Side::Right, 1));
+
+        // Without win-by there is no deuce.
+        let classic = Rules::default();
+        assert!(!classic.is_deuce((10, 10)));
+    }
+
+    #[test]
+    fn winner_and_loser_serve_rules() {
+        let rules = |serve| Rules {
+            serve,
+            ..Rules::default()
+        };
+        let after_left_scores =
+            |serve| rules(serve).next_server(Side::Right, 1, Side::Left, (1, 0));
+        assert_eq!(after_left_scores(ServeRule::Winner), (Side::Left, 1));
+        assert_eq!(after_left_scores(ServeRule::Loser), (Side::Right, 1));
+    }
+
+    #[test]
+    fn loading_rejects_duplicate_names_and_invalid_values() {
+        let valid = load(
+            "rules-valid",
+            "[[ruleset]]\nname = \"Quick\"\npoints = 5\n\n[[ruleset]]\nname = \"Long\"\npoints = 21\nwin_by = 2\n",
+        )
+        .unwrap();
+        assert_eq!(valid.rulesets.len(), 2);
+        assert_eq!(valid.rulesets[1].win_by, 2);
+        assert_eq!(valid.rulesets[1].games, 1);
+
+        let duplicate = load(
+            "rules-duplicate",
+            "[[ruleset]]\nname = \"Quick\"\n\n[[ruleset]]\nname = \"Quick\"\npoints = 5\n",
+        );
+        assert!(matches!(
+            duplicate,
+            Err(ConfigError::Invalid(message)) if message == "ruleset \"Quick\" is defined more than once"
+        ));
+
+        for (fields, message) in [
+            ("name = \"Broken\"\npoints = 0", "points must be at least 1"),
+            ("name = \"Broken\"\nwin_by = 0", "win_by must be at least 1"),
+            ("name = \"Broken\"\ngames = 0", "games must be at least 1"),
+            ("name = \"Broken\"\nserves = 0", "serves must be at least 1"),
+            ("name = \" \"", "name must not be empty"),
+        ] {
+            match load("rules-invalid", &format!("[[ruleset]]\n{}\n", fields)) {
+                Err(ConfigError::Invalid(error)) => assert!(error.ends_with(message), "{}", error),
+                other => panic!(
+                    "{:?} loaded as {:?}",
+                    fields,
+                    other.map(|rules| rules.rulesets)
+                ),
+            }
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use std::fs;
+
+use macroquad::prelude::{vec2, Vec2};
+use rhai::{module_resolvers::DummyModuleResolver, Dynamic, Engine, Map, Scope, AST, FLOAT};
+
+use crate::{
+    bounds::Bounds,
+    clock::SimTime,
+    config::FileWatcher,
+    controller::{ControlContext, PaddleController, PaddleIntent},
+    events::Side,
+    physics::GameObject,
+};
+
+// Operations a script may run per tick before it is stopped.
+pub const SCRIPT_OPERATIONS_BUDGET: u64 = 50_000;
+const SCRIPT_ENTRY_POINT: &str = "control";
+
+// Plays a paddle with a Rhai script defining `fn control(state)`. `state` is a copy of the ball,
+// both paddles and the bounds, and the function returns the y the center of the paddle should
+// move to, or nothing to leave the paddle where it is. Scripts can't import modules and are
+// stopped once they run out of operations, leaving the paddle idle for that tick.
+pub struct ScriptController {
+    name: String,
+    path: String,
+    engine: Engine,
+    ast: Option<AST>,
+    watcher: FileWatcher,
+    error: Option<String>,
+    target: Option<f32>,
+}
+
+impl ScriptController {
+    pub fn new(name: &str, path: &str) -> Self {
+        let mut controller = Self {
+            name: name.to_owned(),
+            path: path.to_owned(),
+            engine: sandboxed_engine(),
+            ast: None,
+            watcher: FileWatcher::new(path),
+            error: None,
+            target: None,
+        };
+        controller.reload();
+        controller
+    }
+}
+
+impl ScriptController {
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    // Compiles the script again, keeping the previous version running if the new one is broken.
+    pub fn reload(&mut self) {
+        match compile(&self.engine, &self.path) {
+            Ok(ast) => {
+                self.ast = Some(ast);
+                self.error = None;
+            }
+            Err(error) => self.error = Some(error),
+        }
+    }
+
+    // Reports an error once rather than on every tick it happens.
+    fn fail(&mut self, error: String) {
+        if self.error.as_ref() != Some(&error) {
+            eprintln!("{}: {}", self.name, error);
+        }
+        self.error = Some(error);
+    }
+}
+
+// Checks that a script compiles and defines its entry point, for validating profiles on load.
+pub fn check_script(path: &str) -> Result<(), String> {
+    compile(&sandboxed_engine(), path).map(|_| ())
+}
+
+fn sandboxed_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(SCRIPT_OPERATIONS_BUDGET);
+    engine.set_max_call_levels(32);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(1024);
+    engine.set_max_array_size(1024);
+    engine.set_max_map_size(256);
+    engine.set_module_resolver(DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+    engine
+}
+
+fn compile(engine: &Engine, path: &str) -> Result<AST, String> {
+    let source =
+        fs::read_to_string(path).map_err(|error| format!("could not read {}: {}", path, error))?;
+    let ast = engine
+        .compile(source)
+        .map_err(|error| format!("could not compile {}: {}", path, error))?;
+
+    let has_entry_point = ast
+        .iter_functions()
+        .any(|function| function.name == SCRIPT_ENTRY_POINT && function.params.len() == 1);
+    if !has_entry_point {
+        return Err(format!(
+            "{}: no fn {}(state) defined",
+            path, SCRIPT_ENTRY_POINT
+        ));
+    }
+    Ok(ast)
+}
+
+// Positions are centers, so scripts don't need to know about paddle and ball sizes.
+fn object_map(object: &GameObject) -> Map {
+    let center = object.collider.center();
+    let mut map = Map::new();
+    map.insert("x".into(), Dynamic::from_float(center.x as FLOAT));
+    map.insert("y".into(), Dynamic::from_float(center.y as FLOAT));
+    map.insert("vx".into(), Dynamic::from_float(object.velocity.x as FLOAT));
+    map.insert("vy".into(), Dynamic::from_float(object.velocity.y as FLOAT));
+    map.insert(
+        "w".into(),
+        Dynamic::from_float(object.collider.rect.w as FLOAT),
+    );
+    map.insert(
+        "h".into(),
+        Dynamic::from_float(object.collider.rect.h as FLOAT),
+    );
+    map
+}
+
+fn bounds_map(bounds: Bounds) -> Map {
+    let mut map = Map::new();
+    map.insert("x".into(), Dynamic::from_float(bounds.x as FLOAT));
+    map.insert("y".into(), Dynamic::from_float(bounds.y as FLOAT));
+    map.insert("w".into(), Dynamic::from_float(bounds.w as FLOAT));
+    map.insert("h".into(), Dynamic::from_float(bounds.h as FLOAT));
+    map
+}
+
+fn script_state(context: &ControlContext) -> Map {
+    let mut ball = object_map(context.ball);
+    ball.insert(
+        "spin".into(),
+        Dynamic::from_float(context.ball.angular_velocity as FLOAT),
+    );
+    let side = match context.side {
+        Side::Left => "left",
+        Side::Right => "right",
+    };
+
+    let mut state = Map::new();
+    state.insert("side".into(), side.into());
+    state.insert(
+        "time".into(),
+        Dynamic::from_float(context.now.since(SimTime::ZERO) as FLOAT),
+    );
+    state.insert("ball".into(), ball.into());
+    state.insert("paddle".into(), object_map(context.paddle).into());
+    state.insert("opponent".into(), object_map(context.opponent).into());
+    state.insert("bounds".into(), bounds_map(context.bounds).into());
+    state
+}
+
+impl PaddleController for ScriptController {
+    fn name(&self) -> Option<&str> {
+        Some(&self.name)
+    }
+
+    fn poll(&mut self, _bounds: Bounds, _to_field: &dyn Fn(Vec2) -> Vec2) {
+        if self.watcher.changed() {
+            self.reload();
+            match &self.error {
+                None => println!("Reloaded script {}", self.path),
+                Some(error) => eprintln!("Keeping previous script, {}", error),
+            }
+        }
+    }
+
+    fn control(&mut self, context: &ControlContext) -> PaddleIntent {
+        let Some(ast) = &self.ast else {
+            return PaddleIntent::Idle;
+        };
+        let result = self.engine.call_fn::<Dynamic>(
+            &mut Scope::new(),
+            ast,
+            SCRIPT_ENTRY_POINT,
+            (script_state(context),),
+        );
+
+        let target = match result {
+            Ok(value) if value.is_unit() => Ok(None),
+            Ok(value) => value
+                .as_float()
+                .ok()
+                .or_else(|| value.as_int().ok().map(|y| y as FLOAT))
+                .filter(|y| y.is_finite())
+                .map(|y| Some(y as f32))
+                .ok_or_else(|| {
+                    format!(
+                        "{} returned {} instead of a number",
+                        SCRIPT_ENTRY_POINT,
+                        value.type_name()
+                    )
+                }),
+            Err(error) => Err(error.to_string()),
+        };
+        self.target = match target {
+            Ok(target) => {
+                self.error = None;
+                target
+            }
+            Err(error) => {
+                self.fail(error);
+                None
+            }
+        };
+
+        match self.target {
+            Some(y) => {
+                let paddle = context.paddle;
+                let top = y - paddle.collider.rect.h / 2.0;
+                PaddleIntent::move_to(vec2(paddle.position.x, top))
+            }
+            None => PaddleIntent::Idle,
+        }
+    }
+
+    fn status(&self) -> Option<String> {
+        let target = match self.target {
+            Some(y) => format!("Target y {:.0}", y),
+            None => "Idle".to_owned(),
+        };
+        let mut status = format!("Script {}\n{}", self.path, target);
+        if let Some(error) = &self.error {
+            status.push('\n');
+            status.push_str(error);
+        }
+        Some(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        constants::{BALL_RADIUS, BOUNDS, PLAYER_HEIGHT, PLAYER_WIDTH},
+        physics::ColliderType,
+    };
+
+    fn script(name: &str, source: &str) -> ScriptController {
+        let path = std::env::temp_dir().join(format!("{}-{}.rhai", name, std::process::id()));
+        fs::write(&path, source).unwrap();
+        let controller = ScriptController::new(name, path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        controller
+    }
+
+    fn control(controller: &mut ScriptController) -> PaddleIntent {
+        let paddle = GameObject::from_pos(
+            50.0,
+            465.0,
+            ColliderType::Rectangle(PLAYER_WIDTH, PLAYER_HEIGHT),
+        );
+        let opponent = GameObject::from_pos(
+            1135.0,
+            465.0,
+            ColliderType::Rectangle(PLAYER_WIDTH, PLAYER_HEIGHT),
+        );
+        let ball = GameObject::from_pos(600.0, 500.0, ColliderType::Circle(BALL_RADIUS));
+        controller.control(&ControlContext {
+            side: Side::Left,
+            paddle: &paddle,
+            opponent: &opponent,
+            ball: &ball,
+            walls: &[],
+            events: &[],
+            bounds: BOUNDS,
+            now: SimTime::ZERO,
+        })
+    }
+
+    #[test]
+    fn script_returning_a_number_moves_the_paddle() {
+        let mut controller = script("number", "fn control(state) { state.ball.y }");
+        assert!(matches!(
+            control(&mut controller),
+            PaddleIntent::MoveTo { .. }
+        ));
+        assert_eq!(controller.error(), None);
+    }
+
+    #[test]
+    fn endless_script_runs_out_of_operations_and_idles() {
+        let mut controller = script("endless", "fn control(state) { loop {} }");
+        assert!(matches!(control(&mut controller), PaddleIntent::Idle));
+        assert!(controller.error().unwrap().contains("operations"));
+    }
+
+    #[test]
+    fn compile_error_is_reported() {
+        let mut controller = script("broken", "fn control(state) { state.ball.y +");
+        assert!(controller.error().unwrap().contains("could not compile"));
+        assert!(matches!(control(&mut controller), PaddleIntent::Idle));
+    }
+
+    #[test]
+    fn non_numeric_result_is_rejected() {
+        let mut controller = script("text", "fn control(state) { \"up\" }");
+        assert!(matches!(control(&mut controller), PaddleIntent::Idle));
+        assert!(controller.error().unwrap().contains("instead of a number"));
+    }
+}
@@ -0,0 +1,123 @@
+use serde::Serialize;
+
+use crate::{ai::PredictionMode, events::Side};
+
+// Older rallies are dropped past this many, so a long session doesn't grow the log forever.
+const MAX_LOGGED_RALLIES: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Pending,
+    Hit,
+    Missed,
+}
+
+// One read of the ball by an AI: what it saw, what it predicted and how it chose to play it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Decision {
+    pub time: f64,
+    pub ball_position: (f32, f32),
+    pub ball_velocity: (f32, f32),
+    pub ball_spin: f32,
+    pub prediction: PredictionMode,
+    pub predicted_position: (f32, f32),
+    pub hit_position: f32,
+    // Factor applied to the vertical speed of the ball when predicting.
+    pub accuracy_variation: f32,
+    pub reaction_delay: f64,
+    pub tiredness: f32,
+    pub overshoot_offset: f32,
+    pub outcome: Outcome,
+    // Where the ball was when it hit the paddle or scored, to compare with the prediction.
+    pub outcome_position: Option<(f32, f32)>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Rally {
+    pub number: u32,
+    pub scorer: Option<Side>,
+    pub decisions: Vec<Decision>,
+}
+
+impl Rally {
+    fn new(number: u32) -> Self {
+        Self {
+            number,
+            scorer: None,
+            decisions: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DecisionLog {
+    pub side: Option<Side>,
+    pub rallies: Vec<Rally>,
+}
+
+impl DecisionLog {
+    pub fn new() -> Self {
+        Self {
+            side: None,
+            rallies: vec![Rally::new(1)],
+        }
+    }
+}
+
+impl Default for DecisionLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DecisionLog {
+    pub fn record(&mut self, side: Side, decision: Decision) {
+        self.side = Some(side);
+        self.current_rally().decisions.push(decision);
+    }
+
+    // Settles the latest decision of the rally, if it is still waiting for the ball.
+    pub fn resolve(&mut self, outcome: Outcome, ball_position: (f32, f32)) {
+        if let Some(decision) = self.current_rally().decisions.last_mut() {
+            if decision.outcome == Outcome::Pending {
+                decision.outcome = outcome;
+                decision.outcome_position = Some(ball_position);
+            }
+        }
+    }
+
+    pub fn end_rally(&mut self, scorer: Side) {
+        let rally = self.current_rally();
+        rally.scorer = Some(scorer);
+        let number = rally.number + 1;
+
+        self.rallies.push(Rally::new(number));
+        if self.rallies.len() > MAX_LOGGED_RALLIES {
+            self.rallies.remove(0);
+        }
+    }
+
+    fn current_rally(&mut self) -> &mut Rally {
+        self.rallies
+            .last_mut()
+            .expect("a log always has a current rally")
+    }
+}
+
+#[derive(Serialize)]
+struct NamedLog<'a> {
+    name: &'a str,
+    #[serde(flatten)]
+    log: &'a DecisionLog,
+}
+
+// Writes the logs of several AIs as one JSON array.
+pub fn save_logs(path: &str, logs: &[(&str, &DecisionLog)]) -> Result<(), String> {
+    let logs: Vec<NamedLog> = logs
+        .iter()
+        .map(|&(name, log)| NamedLog { name, log })
+        .collect();
+    let json = serde_json::to_string_pretty(&logs).map_err(|error| error.to_string())?;
+    std::fs::write(path, json).map_err(|error| format!("could not write {}: {}", path, error))
+}
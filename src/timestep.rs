@@ -0,0 +1,34 @@
+pub struct FixedTimestep {
+    pub tick: f32,
+    pub max_frame_time: f32,
+    accumulator: f32,
+}
+
+impl FixedTimestep {
+    pub fn new(tick_rate: f32) -> Self {
+        Self {
+            tick: 1.0 / tick_rate,
+            max_frame_time: 0.25,
+            accumulator: 0.0,
+        }
+    }
+}
+
+impl FixedTimestep {
+    // Returns how many ticks should be simulated for this frame.
+    pub fn accumulate(&mut self, frame_time: f32) -> u32 {
+        self.accumulator += frame_time.min(self.max_frame_time);
+
+        let mut ticks = 0;
+        while self.accumulator >= self.tick {
+            self.accumulator -= self.tick;
+            ticks += 1;
+        }
+        ticks
+    }
+
+    // How far we are between the previous and the current tick, used to interpolate rendering.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / self.tick
+    }
+}
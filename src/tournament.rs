@@ -0,0 +1,271 @@
+use std::cmp::Ordering;
+
+use serde::Serialize;
+
+use crate::{
+    ai::AiProfile, clock::SimTime, events::GameEvent, game::GameState, physics::PhysicsConfig,
+    rules::Rules,
+};
+
+pub static INITIAL_ELO: f64 = 1500.0;
+pub static ELO_K_FACTOR: f64 = 16.0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct MatchSettings {
+    // Points needed to win the match.
+    pub points: u32,
+    // Simulated seconds after which the match stops, so two AIs that never miss still finish.
+    pub max_time: f64,
+    pub physics: PhysicsConfig,
+}
+
+impl Default for MatchSettings {
+    fn default() -> Self {
+        Self {
+            points: 11,
+            max_time: 600.0,
+            physics: PhysicsConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchResult {
+    pub left: String,
+    pub right: String,
+    pub seed: u64,
+    pub score: (u32, u32),
+    pub winner: Option<String>,
+    pub duration: f64,
+    pub rallies: u32,
+    pub hits: u32,
+    pub longest_rally: u32,
+}
+
+impl MatchResult {
+    // 1.0 if the left player won, 0.0 if they lost and 0.5 for a draw.
+    pub fn left_outcome(&self) -> f64 {
+        match self.score.0.cmp(&self.score.1) {
+            Ordering::Greater => 1.0,
+            Ordering::Less => 0.0,
+            Ordering::Equal => 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Standing {
+    pub name: String,
+    pub played: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub points_for: u32,
+    pub points_against: u32,
+    pub rallies: u32,
+    pub hits: u32,
+    pub longest_rally: u32,
+    pub elo: f64,
+}
+
+impl Standing {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            played: 0,
+            wins: 0,
+            draws: 0,
+            losses: 0,
+            points_for: 0,
+            points_against: 0,
+            rallies: 0,
+            hits: 0,
+            longest_rally: 0,
+            elo: INITIAL_ELO,
+        }
+    }
+}
+
+impl Standing {
+    pub fn win_rate(&self) -> f64 {
+        if self.played == 0 {
+            return 0.0;
+        }
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.played as f64
+    }
+
+    // Average number of paddle hits per rally in this player's matches.
+    pub fn average_rally(&self) -> f64 {
+        if self.rallies == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / self.rallies as f64
+    }
+
+    fn record(&mut self, result: &MatchResult, scored: u32, conceded: u32) {
+        self.played += 1;
+        match scored.cmp(&conceded) {
+            Ordering::Greater => self.wins += 1,
+            Ordering::Less => self.losses += 1,
+            Ordering::Equal => self.draws += 1,
+        }
+        self.points_for += scored;
+        self.points_against += conceded;
+        self.rallies += result.rallies;
+        self.hits += result.hits;
+        self.longest_rally = self.longest_rally.max(result.longest_rally);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Tournament {
+    pub games: u32,
+    pub seed: u64,
+    pub points: u32,
+    pub max_time: f64,
+    pub standings: Vec<Standing>,
+    pub matches: Vec<MatchResult>,
+}
+
+impl Tournament {
+    // Every pair of profiles plays `games` matches on each side, the n-th match of a pairing
+    // using `seed + n` on both sides so they face the same serves.
+    pub fn round_robin(
+        profiles: &[AiProfile],
+        games: u32,
+        seed: u64,
+        settings: MatchSettings,
+    ) -> Self {
+        let mut standings: Vec<Standing> = profiles
+            .iter()
+            .map(|profile| Standing::new(&profile.name))
+            .collect();
+        let mut matches = Vec::new();
+
+        for game in 0..games {
+            let match_seed = seed.wrapping_add(game as u64);
+            for i in 0..profiles.len() {
+                for j in (i + 1)..profiles.len() {
+                    for (left, right) in [(i, j), (j, i)] {
+                        let result =
+                            play_match(&profiles[left], &profiles[right], match_seed, settings);
+
+                        let (left_elo, right_elo) = update_elo(
+                            standings[left].elo,
+                            standings[right].elo,
+                            result.left_outcome(),
+                        );
+                        standings[left].elo = left_elo;
+                        standings[right].elo = right_elo;
+                        standings[left].record(&result, result.score.0, result.score.1);
+                        standings[right].record(&result, result.score.1, result.score.0);
+
+                        matches.push(result);
+                    }
+                }
+            }
+        }
+
+        standings.sort_by(|a, b| b.elo.total_cmp(&a.elo));
+
+        Self {
+            games,
+            seed,
+            points: settings.points,
+            max_time: settings.max_time,
+            standings,
+            matches,
+        }
+    }
+}
+
+pub fn play_match(
+    left: &AiProfile,
+    right: &AiProfile,
+    seed: u64,
+    settings: MatchSettings,
+) -> MatchResult {
+    let mut state = GameState::new(left.controller(), right.controller(), seed);
+    state.rules = Rules::first_to(settings.points);
+    state.apply_physics(&settings.physics);
+    let end = SimTime::from_secs(settings.max_time);
+    let dt = state.timestep.tick;
+
+    let mut rallies = 0;
+    let mut hits = 0;
+    let mut rally_hits = 0;
+    let mut longest_rally = 0;
+
+    while state.clock.now() < end && state.winner.is_none() {
+        for event in state.step(dt) {
+            match event {
+                GameEvent::PaddleHit { .. } => {
+                    hits += 1;
+                    rally_hits += 1;
+                    longest_rally = longest_rally.max(rally_hits);
+                }
+                GameEvent::Goal { .. } => {
+                    rallies += 1;
+                    rally_hits = 0;
+                }
+                _ => {}
+            }
+        }
+    }
+    // A rally cut short by the time limit still counts towards the averages.
+    if rally_hits > 0 {
+        rallies += 1;
+    }
+
+    let score = (state.player_left.score, state.player_right.score);
+    let winner = match score.0.cmp(&score.1) {
+        Ordering::Greater => Some(left.name.clone()),
+        Ordering::Less => Some(right.name.clone()),
+        Ordering::Equal => None,
+    };
+
+    MatchResult {
+        left: left.name.clone(),
+        right: right.name.clone(),
+        seed,
+        score,
+        winner,
+        duration: state.clock.now().since(SimTime::ZERO),
+        rallies,
+        hits,
+        longest_rally,
+    }
+}
+
+// Returns the new ratings of two players after a match, `outcome` being the first player's
+// result (1.0 win, 0.5 draw, 0.0 loss).
+pub fn update_elo(rating: f64, opponent_rating: f64, outcome: f64) -> (f64, f64) {
+    let expected = 1.0 / (1.0 + 10f64.powf((opponent_rating - rating) / 400.0));
+    let change = ELO_K_FACTOR * (outcome - expected);
+    (rating + change, opponent_rating - change)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elo_moves_points_from_the_loser_to_the_winner() {
+        let (winner, loser) = update_elo(1600.0, 1450.0, 1.0);
+        assert!(winner > 1600.0 && loser < 1450.0);
+        assert!(((winner - 1600.0) + (loser - 1450.0)).abs() < 1e-9);
+
+        let (loser, winner) = update_elo(1600.0, 1450.0, 0.0);
+        assert!(((1600.0 - loser) - (winner - 1450.0)).abs() < 1e-9);
+        // An upset costs the favourite more than a win earns them.
+        assert!(1600.0 - loser > ELO_K_FACTOR / 2.0);
+    }
+
+    #[test]
+    fn draw_between_equal_ratings_changes_nothing() {
+        assert_eq!(
+            update_elo(INITIAL_ELO, INITIAL_ELO, 0.5),
+            (INITIAL_ELO, INITIAL_ELO)
+        );
+    }
+}